@@ -62,4 +62,30 @@ export class Configuration {
         }
         return d;
     }
+
+    /** Directories contains the Rust code of `extern` functions, relative to the workspace. */
+    rustDirs(): string[] {
+        const d = this.configuration.get<string[]>('rustDirs');
+        if (d === undefined) {
+            return [];
+        }
+        const root = vscode.workspace.workspaceFolders?.[0]?.uri.fsPath ?? '';
+        return d.map((x) => Path.resolve(root, x));
+    }
+
+    /** Settings sent to the language server, see `ServerConfig` of the server. */
+    serverSettings(): object {
+        const b = (key: string, d: boolean): boolean => this.configuration.get<boolean>(key, d);
+        return {
+            rustDirs: this.rustDirs(),
+            lints: this.configuration.get<object>('lints', {}),
+            inlayHints: {
+                variableTypes: b('inlayHints.variableTypes', true),
+                implicitConversions: b('inlayHints.implicitConversions', true),
+                argumentTypes: b('inlayHints.argumentTypes', false),
+                returnTypes: b('inlayHints.returnTypes', false),
+                rulePriorities: b('inlayHints.rulePriorities', false),
+            },
+        };
+    }
 }
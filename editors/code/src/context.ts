@@ -125,6 +125,7 @@ export class Context {
     const clientOptions: lc.LanguageClientOptions = {
       documentSelector: [{ scheme: 'file', language: 'isle' }],
       traceOutputChannel,
+      initializationOptions: this.configuration.serverSettings(),
     };
 
     const client = new lc.LanguageClient(
@@ -8,7 +8,9 @@ import { Extension } from './extension';
 import { log } from './log';
 
 import * as childProcess from 'child_process';
+import * as Path from 'path';
 import * as vscode from 'vscode';
+import type * as lc from 'vscode-languageclient';
 
 /**
  * An extension command that displays the version of the server that this extension
@@ -64,6 +66,8 @@ export async function activate(
     return;
   }
 
+  // Same as the legend the server sends in `initialize`,
+  // see `ts_code` in `semantic_tokens.rs`.
   const tokenTypes = ['struct',
     'function',
     'variable',
@@ -72,26 +76,74 @@ export async function activate(
     'operator',
     'enumMember',
     'type',
-    'number'];
-  const tokenModifiers = ['declaration'];
+    'number',
+    'property',
+    'extractor',
+    'constructor',
+    'term',
+    'unresolved'];
+  const tokenModifiers = ['declaration', 'definition', 'readonly', 'extern'];
   const legend = new vscode.SemanticTokensLegend(tokenTypes, tokenModifiers);
-  const provider: vscode.DocumentSemanticTokensProvider = {
-    provideDocumentSemanticTokens(
+  interface LspSemanticTokens { resultId?: string; data: number[] }
+  interface LspSemanticTokensDelta {
+    resultId?: string;
+    edits: { start: number; deleteCount: number; data?: number[] }[];
+  }
+  const toSemanticTokens = (r: LspSemanticTokens | null): vscode.SemanticTokens | undefined => {
+    if (r === null) {
+      return undefined;
+    }
+    return new vscode.SemanticTokens(new Uint32Array(r.data), r.resultId);
+  };
+  const provider: vscode.DocumentSemanticTokensProvider & vscode.DocumentRangeSemanticTokensProvider = {
+    async provideDocumentSemanticTokens(
       document: vscode.TextDocument,
-    ): vscode.ProviderResult<vscode.SemanticTokens> {
+    ): Promise<vscode.SemanticTokens | undefined> {
       // Analyze the document and return semantic tokens
       const client = context.getClient();
       if (client === undefined) {
         return undefined;
       }
-      return client.sendRequest<vscode.SemanticTokens>('textDocument/semanticTokens/full',
+      const r = await client.sendRequest<LspSemanticTokens | null>('textDocument/semanticTokens/full',
         { 'textDocument': { uri: document.uri.toString() } });
-
+      return toSemanticTokens(r);
+    },
+    async provideDocumentSemanticTokensEdits(
+      document: vscode.TextDocument,
+      previousResultId: string,
+    ): Promise<vscode.SemanticTokens | vscode.SemanticTokensEdits | undefined> {
+      const client = context.getClient();
+      if (client === undefined) {
+        return undefined;
+      }
+      const r = await client.sendRequest<LspSemanticTokens | LspSemanticTokensDelta | null>(
+        'textDocument/semanticTokens/full/delta',
+        { 'textDocument': { uri: document.uri.toString() }, 'previousResultId': previousResultId });
+      if (r !== null && 'edits' in r) {
+        return new vscode.SemanticTokensEdits(r.edits.map((e) => new vscode.SemanticTokensEdit(
+          e.start, e.deleteCount, e.data === undefined ? undefined : new Uint32Array(e.data))), r.resultId);
+      }
+      return toSemanticTokens(r);
+    },
+    async provideDocumentRangeSemanticTokens(
+      document: vscode.TextDocument,
+      range: vscode.Range,
+    ): Promise<vscode.SemanticTokens | undefined> {
+      const client = context.getClient();
+      if (client === undefined) {
+        return undefined;
+      }
+      const r = await client.sendRequest<LspSemanticTokens | null>('textDocument/semanticTokens/range',
+        { 'textDocument': { uri: document.uri.toString() }, 'range': range });
+      return toSemanticTokens(r);
     },
   };
   vscode.languages.registerDocumentSemanticTokensProvider({ language: 'isle', scheme: 'file' },
     provider,
     legend);
+  vscode.languages.registerDocumentRangeSemanticTokensProvider({ language: 'isle', scheme: 'file' },
+    provider,
+    legend);
 
   // Register handlers for VS Code commands that the user explicitly issues.
   context.registerCommand('serverVersion', serverVersion);
@@ -127,6 +179,162 @@ export async function activate(
 
   });
 
+  context.registerCommand('isle.explain.overlap', () => {
+    const client = context.getClient();
+    if (client === undefined) {
+      return;
+    }
+    const d = vscode.window.activeTextEditor;
+    if (d === undefined) {
+      return;
+    }
+    const fpath = d.document.uri.fsPath;
+    const line = d.selection.active.line;
+    const col = d.selection.active.character;
+    client.sendRequest<{
+      rule: { uri: string; range: vscode.Range };
+      witness: string;
+    }[]>('isle/explain_overlap', { 'fpath': fpath, 'line': line, 'col': col }).then((r) => {
+      const content = r.map((x) =>
+        `;; overlaps with the rule at ${vscode.Uri.parse(x.rule.uri).fsPath}:${x.rule.range.start.line + 1}\n` +
+        `;; both rules match:\n${x.witness}\n`).join('\n');
+      void vscode.workspace.openTextDocument({ language: 'isle', content: content }).then((e) => {
+        void vscode.window.showTextDocument(e, { viewColumn: vscode.ViewColumn.Beside });
+      });
+    }).catch((e) => {
+      void vscode.window.showErrorMessage('explain overlap failed:' + (e as string));
+    });
+  });
+
+  // Sent by the "Change signature" code action.
+  context.registerCommand('isle.change.signature', async (_context, ...args) => {
+    const client = context.getClient();
+    if (client === undefined) {
+      return;
+    }
+    const req = args[0] as { fpath: string; line: number; col: number; name: string; arg_tys: string[] };
+    const input = await vscode.window.showInputBox({
+      title: `Change signature of \`${req.name}\``,
+      prompt: '`$N` keeps the argument N,a type adds a new argument.',
+      value: req.arg_tys.map((_x, index) => `$${index}`).join(' '),
+    });
+    if (input === undefined) {
+      return;
+    }
+    const newArgs = input.split(/\s+/).filter((x) => x.length > 0).map((x) =>
+      /^\$\d+$/.test(x) ? { 'old': parseInt(x.substring(1)) } : { 'new': x });
+    client.sendRequest<lc.WorkspaceEdit>('isle/change_signature',
+      { 'fpath': req.fpath, 'line': req.line, 'col': req.col, 'args': newArgs }).then(async (r) => {
+      const edit = await client.protocol2CodeConverter.asWorkspaceEdit(r);
+      await vscode.workspace.applyEdit(edit);
+    }).catch((e) => {
+      void vscode.window.showErrorMessage('change signature failed:' + (e as string));
+    });
+  });
+
+  // Sent by the "Move to another file" code action.
+  context.registerCommand('isle.move.decl', async (_context, ...args) => {
+    const client = context.getClient();
+    if (client === undefined) {
+      return;
+    }
+    const req = args[0] as { fpath: string; line: number; col: number; name: string };
+    const root = vscode.workspace.workspaceFolders?.[0]?.uri.fsPath ?? '';
+    const newFile = 'New file...';
+    const files = context.configuration.isleFiles();
+    const picked = await vscode.window.showQuickPick(
+      files.filter((x) => Path.resolve(root, x) !== req.fpath).concat([newFile]),
+      { placeHolder: `Move \`${req.name}\` to` });
+    if (picked === undefined) {
+      return;
+    }
+    const to = picked === newFile ? await vscode.window.showInputBox({
+      prompt: 'Path of the new file,relative to the workspace.',
+    }) : picked;
+    if (to === undefined || to.length === 0) {
+      return;
+    }
+    client.sendRequest<lc.WorkspaceEdit>('isle/move_decl',
+      { 'fpath': req.fpath, 'line': req.line, 'col': req.col, 'to': Path.resolve(root, to) }).then(async (r) => {
+      const edit = await client.protocol2CodeConverter.asWorkspaceEdit(r);
+      await vscode.workspace.applyEdit(edit);
+      await vscode.workspace.saveAll(false);
+      const projectFiles = picked === newFile ? files.concat([to]) : files;
+      if (picked === newFile) {
+        // Add the new file to the project.
+        await vscode.workspace.getConfiguration('isle-analyzer').update('files', projectFiles);
+      }
+      await client.sendRequest('isle/reload', { 'files': projectFiles });
+    }).catch((e) => {
+      void vscode.window.showErrorMessage('move decl failed:' + (e as string));
+    });
+  });
+
+  // Read-only documents of `isle/expand_rule`.
+  const expanded = new Map<string, string>();
+  extensionContext.subscriptions.push(vscode.workspace.registerTextDocumentContentProvider('isle-expanded', {
+    provideTextDocumentContent(uri: vscode.Uri): string {
+      return expanded.get(uri.toString()) ?? '';
+    },
+  }));
+  context.registerCommand('isle.expand.rule', () => {
+    const client = context.getClient();
+    if (client === undefined) {
+      return;
+    }
+    const d = vscode.window.activeTextEditor;
+    if (d === undefined) {
+      return;
+    }
+    const fpath = d.document.uri.fsPath;
+    const line = d.selection.active.line;
+    const col = d.selection.active.character;
+    client.sendRequest<{
+      rule: { uri: string; range: vscode.Range };
+      text: string;
+    }>('isle/expand_rule', { 'fpath': fpath, 'line': line, 'col': col }).then(async (r) => {
+      const uri = vscode.Uri.parse(
+        `isle-expanded:${Path.basename(fpath)}-${r.rule.range.start.line + 1}.isle`);
+      expanded.set(uri.toString(), r.text);
+      const e = await vscode.workspace.openTextDocument(uri);
+      await vscode.languages.setTextDocumentLanguage(e, 'isle');
+      await vscode.window.showTextDocument(e, { viewColumn: vscode.ViewColumn.Beside });
+    }).catch((e) => {
+      void vscode.window.showErrorMessage('expand rule failed:' + (e as string));
+    });
+  });
+
+  context.registerCommand('isle.dead.code.report', () => {
+    const client = context.getClient();
+    if (client === undefined) {
+      return;
+    }
+    interface DeadCode {
+      kind: string;
+      name: string;
+      location: { uri: string; range: vscode.Range };
+    }
+    client.sendRequest<DeadCode[]>('isle/dead_code_report', {}).then(async (r) => {
+      if (r.length === 0) {
+        void vscode.window.showInformationMessage('No unused items.');
+        return;
+      }
+      const picked = await vscode.window.showQuickPick(r.map((x) => ({
+        label: x.name,
+        description: x.kind,
+        detail: `${vscode.Uri.parse(x.location.uri).fsPath}:${x.location.range.start.line + 1}`,
+        x,
+      })), { placeHolder: 'Unused items' });
+      if (picked === undefined) {
+        return;
+      }
+      const t = await vscode.workspace.openTextDocument(vscode.Uri.parse(picked.x.location.uri));
+      await vscode.window.showTextDocument(t, { selection: picked.x.location.range });
+    }).catch((e) => {
+      void vscode.window.showErrorMessage('get unused items failed:' + (e as string));
+    });
+  });
+
   const d = vscode.languages.registerInlayHintsProvider({ scheme: 'file', language: 'isle' },
     {
       provideInlayHints(document, range) {
@@ -149,6 +357,18 @@ export async function activate(
 
   reload(context);
 
+  extensionContext.subscriptions.push(vscode.workspace.onDidChangeConfiguration((e) => {
+    if (!e.affectsConfiguration('isle-analyzer')) {
+      return;
+    }
+    const client = context.getClient();
+    if (client === undefined) {
+      return;
+    }
+    void client.sendNotification('workspace/didChangeConfiguration',
+      { settings: new Configuration().serverSettings() });
+  }));
+
 
 }
 
@@ -4,8 +4,20 @@ use clap::Parser;
 use crossbeam::channel::select;
 use isle_analyzer::reload;
 use isle_analyzer::{
-    completion::on_completion_request, context::*, document_symbol, goto_definition, hover,
-    inlay_hitnt, project::Project, references, rename::on_rename, semantic_tokens, show_rust_code,
+    change_signature, code_action,
+    completion::on_completion_request,
+    config::ServerConfig,
+    context::*,
+    dead_code, document_highlight, document_symbol, expand_rule, folding_range, goto_definition,
+    hover, inlay_hitnt,
+    lint::{lint_diagnostics, lints},
+    move_decl,
+    overlap::{self, overlap_witness},
+    prio_fix,
+    project::Project,
+    references,
+    rename::{on_prepare_rename, on_rename},
+    selection_range, semantic_tokens, show_rust_code,
 };
 use log::*;
 use lsp_types::notification::Notification;
@@ -38,10 +50,27 @@ use lsp_server::*;
 
 #[derive(Parser)]
 #[clap(author, version, about)]
-struct Options {}
+struct Options {
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(clap::Subcommand)]
+enum Commands {
+    /// Run lints over `ISLE` files and print the results.
+    Lint {
+        /// A json file of settings,the same as `initializationOptions`.
+        #[clap(long)]
+        config: Option<PathBuf>,
+        files: Vec<PathBuf>,
+    },
+}
 
 fn main() {
-    Options::parse();
+    let options = Options::parse();
+    if let Some(Commands::Lint { config, files }) = options.command {
+        std::process::exit(run_lint(config, files));
+    }
 
     init_log();
     // stdio is used to communicate Language Server Protocol requests and responses.
@@ -61,12 +90,15 @@ fn main() {
     let mut context = Context {
         connection,
         project: Project::empty(),
+        config: ServerConfig::default(),
+        semantic_tokens: Default::default(),
     };
 
-    let (id, _client_response) = context
+    let (id, client_response) = context
         .connection
         .initialize_start()
         .expect("could not start connection initialization");
+    context.config = ServerConfig::from_value(client_response.get("initializationOptions"));
 
     let capabilities = serde_json::to_value(lsp_types::ServerCapabilities {
         // The server receives notifications from the client as users open, close,
@@ -104,17 +136,27 @@ fn main() {
             },
             completion_item: None,
         }),
-        rename_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Right(RenameOptions {
+            prepare_provider: Some(true),
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: None,
+            },
+        })),
         definition_provider: Some(OneOf::Left(true)),
         type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
         references_provider: Some(OneOf::Left(true)),
         document_symbol_provider: Some(OneOf::Left(true)),
+        document_highlight_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
         inlay_hint_provider: Some(OneOf::Left(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
         semantic_tokens_provider: Some(
             lsp_types::SemanticTokensServerCapabilities::SemanticTokensOptions(
                 lsp_types::SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
                     range: Some(true),
-                    full: None,
+                    full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
                     ..Default::default()
                 },
             ),
@@ -169,22 +211,58 @@ fn on_request(context: &mut Context, request: &lsp_server::Request) {
         lsp_types::request::HoverRequest::METHOD => {
             hover::on_hover_request(context, request);
         }
+        lsp_types::request::DocumentHighlightRequest::METHOD => {
+            document_highlight::on_document_highlight_request(context, request);
+        }
+        lsp_types::request::FoldingRangeRequest::METHOD => {
+            folding_range::on_folding_range_request(context, request);
+        }
+        lsp_types::request::SelectionRangeRequest::METHOD => {
+            selection_range::on_selection_range_request(context, request);
+        }
         lsp_types::request::DocumentSymbolRequest::METHOD => {
             document_symbol::on_document_symbol_request(context, request);
         }
         lsp_types::request::SemanticTokensFullRequest::METHOD => {
-            semantic_tokens::on_senantic_tokens(context, request);
+            semantic_tokens::on_semantic_tokens_full(context, request);
+        }
+        lsp_types::request::SemanticTokensFullDeltaRequest::METHOD => {
+            semantic_tokens::on_semantic_tokens_full_delta(context, request);
+        }
+        lsp_types::request::SemanticTokensRangeRequest::METHOD => {
+            semantic_tokens::on_semantic_tokens_range(context, request);
         }
         lsp_types::request::InlayHintRequest::METHOD => {
             inlay_hitnt::on_inlay_hints(context, request);
         }
+        lsp_types::request::CodeActionRequest::METHOD => {
+            code_action::on_code_action(context, request);
+        }
         lsp_types::request::Rename::METHOD => {
             on_rename(context, request);
         }
+        lsp_types::request::PrepareRenameRequest::METHOD => {
+            on_prepare_rename(context, request);
+        }
         "isle/reload" => {
             reload::on_reload(context, request);
             send_diag(context);
         }
+        "isle/dead_code_report" => {
+            dead_code::on_dead_code_report(context, request);
+        }
+        "isle/change_signature" => {
+            change_signature::on_change_signature(context, request);
+        }
+        "isle/move_decl" => {
+            move_decl::on_move_decl(context, request);
+        }
+        "isle/expand_rule" => {
+            expand_rule::on_expand_rule(context, request);
+        }
+        "isle/explain_overlap" => {
+            overlap::on_explain_overlap(context, request);
+        }
         "isle/show_compiled_code" => {
             show_rust_code::on_show_compiled_code(context, request);
         }
@@ -222,10 +300,88 @@ fn on_notification(context: &mut Context, notification: &lsp_server::Notificatio
             );
             send_diag(context);
         }
+        lsp_types::notification::DidChangeConfiguration::METHOD => {
+            let parameters =
+                serde_json::from_value::<DidChangeConfigurationParams>(notification.params.clone())
+                    .expect("could not deserialize DidChangeConfigurationParams request");
+            // settings may be the whole configuration of the client.
+            let settings = parameters
+                .settings
+                .get("isle-analyzer")
+                .unwrap_or(&parameters.settings);
+            context.config = ServerConfig::from_value(Some(settings));
+            // severities of lints may changed.
+            send_diag(context);
+        }
         _ => log::error!("handle request '{}' from client", notification.method),
     }
 }
 
+/// Print lint results,return the exit code.
+fn run_lint(config: Option<PathBuf>, files: Vec<PathBuf>) -> i32 {
+    let config = match config {
+        Some(path) => {
+            let value = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|x| {
+                    serde_json::from_str::<serde_json::Value>(&x).map_err(|err| err.to_string())
+                });
+            match value {
+                Ok(x) => ServerConfig::from_value(Some(&x)),
+                Err(err) => {
+                    eprintln!("read config {:?} failed:{}", path, err);
+                    return 2;
+                }
+            }
+        }
+        None => ServerConfig::default(),
+    };
+    let files: Vec<_> = files
+        .into_iter()
+        .map(|x| std::fs::canonicalize(&x).unwrap_or(x))
+        .collect();
+    let project = match Project::new(files) {
+        Ok(x) => x,
+        Err(err) => {
+            eprintln!("load project failed:{:?}", err);
+            return 2;
+        }
+    };
+    let mut diags: Vec<_> = lint_diagnostics(&project, &config, &lints())
+        .into_iter()
+        .collect();
+    diags.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut code = 0;
+    for (f, ds) in diags.iter() {
+        for d in ds.iter() {
+            let level = match d.severity {
+                Some(DiagnosticSeverity::ERROR) => {
+                    code = 1;
+                    "error"
+                }
+                Some(DiagnosticSeverity::WARNING) => "warning",
+                Some(DiagnosticSeverity::INFORMATION) => "info",
+                _ => "hint",
+            };
+            let name = match &d.code {
+                Some(NumberOrString::String(x)) => x.clone(),
+                Some(NumberOrString::Number(x)) => x.to_string(),
+                None => String::new(),
+            };
+            println!(
+                "{}:{}:{}: {}[{}]: {}",
+                f.to_str().unwrap(),
+                d.range.start.line + 1,
+                d.range.start.character + 1,
+                level,
+                name,
+                d.message
+            );
+        }
+    }
+    code
+}
+
 fn update_defs(context: &mut Context, fpath: &PathBuf, content: &str) {
     match context.project.update_defs(&fpath, content) {
         Ok(_) => {}
@@ -240,56 +396,37 @@ fn send_diag(context: &mut Context) {
         .iter()
         .map(|x| PathBuf::from_str(x.as_ref()).unwrap())
         .collect::<Vec<_>>();
+    #[derive(Default)]
+    struct Diags {
+        m: HashMap<PathBuf, Vec<Diagnostic>>,
+    }
+    impl Diags {
+        fn insert(&mut self, p: PathBuf, d: Diagnostic) {
+            if let Some(xxx) = self.m.get_mut(&p) {
+                xxx.push(d);
+            } else {
+                self.m.insert(p, vec![d]);
+            }
+        }
+        fn mk_empty(&mut self, p: PathBuf) {
+            if let Some(_) = self.m.get_mut(&p) {
+            } else {
+                self.m.insert(p, vec![]);
+            }
+        }
+    }
+    let mut diags = Diags {
+        m: lint_diagnostics(&context.project, &context.config, &lints()),
+    };
     match cranelift_isle::compile::from_files(
         &files,
         &cranelift_isle::codegen::CodegenOptions {
             exclude_global_allow_pragmas: false,
         },
     ) {
-        Ok(_) => {
-            for f in files.iter() {
-                let ds = lsp_types::PublishDiagnosticsParams::new(
-                    Url::from_file_path(f).unwrap(),
-                    vec![],
-                    None,
-                );
-                context
-                    .connection
-                    .sender
-                    .send(lsp_server::Message::Notification(
-                        lsp_server::Notification {
-                            method: format!(
-                                "{}",
-                                lsp_types::notification::PublishDiagnostics::METHOD
-                            ),
-                            params: serde_json::to_value(ds).unwrap(),
-                        },
-                    ))
-                    .unwrap();
-            }
-        }
+        Ok(_) => {}
         Err(err) => {
             use cranelift_isle::error::Error::*;
-            #[derive(Default)]
-            struct Diags {
-                m: HashMap<PathBuf, Vec<Diagnostic>>,
-            }
-            impl Diags {
-                fn insert(&mut self, p: PathBuf, d: Diagnostic) {
-                    if let Some(xxx) = self.m.get_mut(&p) {
-                        xxx.push(d);
-                    } else {
-                        self.m.insert(p, vec![d]);
-                    }
-                }
-                fn mk_empty(&mut self, p: PathBuf) {
-                    if let Some(_) = self.m.get_mut(&p) {
-                    } else {
-                        self.m.insert(p, vec![]);
-                    }
-                }
-            }
-            let mut diags = Diags::default();
             for e in err.errors.iter() {
                 match e {
                     IoError {
@@ -337,12 +474,39 @@ fn send_diag(context: &mut Context) {
                     OverlapError { msg, rules } => {
                         for r in rules.iter() {
                             let file = files[r.to.file].clone();
+                            // a input both rules match.
+                            let related: Vec<_> = rules
+                                .iter()
+                                .filter(|x| x.from != r.from)
+                                .filter_map(|x| {
+                                    let witness =
+                                        overlap_witness(&context.project, &r.from, &x.from)?;
+                                    Some(DiagnosticRelatedInformation {
+                                        location: Location {
+                                            uri: Url::from_file_path(&files[x.from.file]).ok()?,
+                                            range: Range {
+                                                start: pos_to_position(x.from),
+                                                end: pos_to_position(x.to),
+                                            },
+                                        },
+                                        message: format!("both rules match {}", witness),
+                                    })
+                                })
+                                .collect();
                             let d = Diagnostic {
                                 range: Range {
                                     start: pos_to_position(r.from),
                                     end: pos_to_position(r.to),
                                 },
                                 message: format!("{}", msg),
+                                code: Some(NumberOrString::String(
+                                    prio_fix::OVERLAP_CODE.to_string(),
+                                )),
+                                related_information: if !related.is_empty() {
+                                    Some(related)
+                                } else {
+                                    None
+                                },
                                 ..Default::default()
                             };
                             diags.insert(file, d);
@@ -357,6 +521,9 @@ fn send_diag(context: &mut Context) {
                                     end: pos_to_position(r.to),
                                 },
                                 message: format!("{}","The rules can never match because another rule will always match first."),
+                                code: Some(NumberOrString::String(
+                                    prio_fix::SHADOWED_CODE.to_string(),
+                                )),
                                 ..Default::default()
                             };
                             diags.insert(file, d);
@@ -364,34 +531,30 @@ fn send_diag(context: &mut Context) {
                     }
                 };
             }
-
-            for f in files.iter() {
-                if diags.m.get(f).map(|x| x.len()).unwrap_or(0) == 0 {
-                    diags.mk_empty(f.clone());
-                }
-            }
-            for (k, v) in diags.m.into_iter() {
-                context
-                    .connection
-                    .sender
-                    .send(lsp_server::Message::Notification(
-                        lsp_server::Notification {
-                            method: format!(
-                                "{}",
-                                lsp_types::notification::PublishDiagnostics::METHOD
-                            ),
-                            params: serde_json::to_value(PublishDiagnosticsParams {
-                                uri: Url::from_file_path(k).unwrap(),
-                                diagnostics: v,
-                                version: None,
-                            })
-                            .unwrap(),
-                        },
-                    ))
-                    .unwrap();
-            }
         }
     };
+    for f in files.iter() {
+        if diags.m.get(f).map(|x| x.len()).unwrap_or(0) == 0 {
+            diags.mk_empty(f.clone());
+        }
+    }
+    for (k, v) in diags.m.into_iter() {
+        context
+            .connection
+            .sender
+            .send(lsp_server::Message::Notification(
+                lsp_server::Notification {
+                    method: format!("{}", lsp_types::notification::PublishDiagnostics::METHOD),
+                    params: serde_json::to_value(PublishDiagnosticsParams {
+                        uri: Url::from_file_path(k).unwrap(),
+                        diagnostics: v,
+                        version: None,
+                    })
+                    .unwrap(),
+                },
+            ))
+            .unwrap();
+    }
 }
 
 fn pos_to_position(x: Pos) -> Position {
@@ -0,0 +1,330 @@
+//! Change the arguments of a decl,
+//! and every application of the term in rules,extractors and the decl itself.
+
+use super::context::Context;
+use super::item::*;
+use super::project::*;
+use crate::send_err;
+use crate::sexpr::{parse_sexprs, splice, SExpr};
+use cranelift_isle::ast::Decl;
+use cranelift_isle::lexer::Pos;
+use lsp_server::*;
+use lsp_types::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A argument of the new signature.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NewArg {
+    /// The argument at this index of the old signature.
+    Old(usize),
+    /// A new argument of this type.
+    New(String),
+}
+
+#[derive(Clone, serde::Deserialize)]
+struct Req {
+    fpath: String,
+    line: u32,
+    col: u32,
+    args: Vec<NewArg>,
+}
+
+/// Handles `isle/change_signature`,the result is a `WorkspaceEdit`.
+pub fn on_change_signature(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<Req>(request.params.clone())
+        .expect("could not deserialize change signature request");
+    let fpath = PathBuf::from_str(parameters.fpath.as_str()).unwrap();
+    let decl = match decl_at(&context.project, &fpath, parameters.line, parameters.col) {
+        Some(x) => x,
+        None => {
+            send_err(context, "Not a decl".to_string(), request.id.clone());
+            return;
+        }
+    };
+    match change_signature(&context.project, &decl, &parameters.args) {
+        Ok(x) => {
+            let r = Response::new_ok(request.id.clone(), serde_json::to_value(x).unwrap());
+            context
+                .connection
+                .sender
+                .send(Message::Response(r))
+                .unwrap();
+        }
+        Err(err) => send_err(context, err, request.id.clone()),
+    }
+}
+
+/// The decl defined or applied at the position.
+pub(crate) fn decl_at(p: &Project, fpath: &Path, line: u32, col: u32) -> Option<Decl> {
+    let mut handler =
+        super::goto_definition::Handler::new(Url::from_file_path(fpath).ok()?, line, col);
+    p.run_visitor_for_file(fpath, &mut handler);
+    let item = match handler.result_item_or_access? {
+        ItemOrAccess::Item(x) => x,
+        ItemOrAccess::Access(x) => x.def,
+    };
+    match item {
+        Item::Decl { decl, .. } => Some(decl),
+        _ => None,
+    }
+}
+
+pub(crate) fn change_signature(
+    p: &Project,
+    decl: &Decl,
+    args: &[NewArg],
+) -> Result<WorkspaceEdit, String> {
+    for a in args.iter() {
+        if let NewArg::Old(index) = a {
+            if *index >= decl.arg_tys.len() {
+                return Err(format!(
+                    "`{}` has only {} arguments",
+                    decl.term.0,
+                    decl.arg_tys.len()
+                ));
+            }
+        }
+    }
+    let mut handler = Sites {
+        def: decl.term.1,
+        sites: vec![decl.term.1],
+    };
+    p.run_full_visitor(&mut handler);
+    let mut changes = HashMap::new();
+    for fpath in p.mk_file_paths().into_iter() {
+        let content = match p.file_text(&fpath) {
+            Some(x) => x,
+            None => continue,
+        };
+        let file_index = match p.found_file_index(&fpath) {
+            Some(x) => x,
+            None => continue,
+        };
+        let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap())
+            .map_err(|err| format!("parse {:?} failed:{:?}", fpath, err))?;
+        let sites: HashSet<_> = handler
+            .sites
+            .iter()
+            .filter(|x| x.file == file_index)
+            .map(|x| (x.line, x.col))
+            .collect();
+        let mut edits = Vec::new();
+        for f in forms.iter() {
+            let mut taken = Vec::new();
+            collect_atoms(f, &mut taken);
+            let r = Rewriter {
+                content: content.as_str(),
+                name: decl.term.0.as_str(),
+                args,
+                sites: &sites,
+                taken,
+            };
+            let new_text = r.form(f);
+            if new_text.as_str() != f.text(content.as_str()) {
+                edits.push(TextEdit {
+                    range: f.range(),
+                    new_text,
+                });
+            }
+        }
+        if !edits.is_empty() {
+            changes.insert(Url::from_file_path(&fpath).unwrap(), edits);
+        }
+    }
+    Ok(WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Pattern,
+    Expr,
+    /// `(term arg...)` of `(extractor (term arg...) template)`.
+    ExtractorHead,
+    /// `(arg_ty...)` of `(decl term (arg_ty...) ret_ty)`.
+    Signature,
+}
+
+/// Where the term is applied or defined,resolved by the visitor.
+struct Sites {
+    def: Pos,
+    sites: Vec<Pos>,
+}
+
+impl ItemOrAccessHandler for Sites {
+    fn handle_item_or_access(&mut self, _p: &Project, item: &ItemOrAccess) {
+        if let ItemOrAccess::Access(access) = item {
+            let (access_pos, def, _) = access.access_def_loc();
+            if def == self.def {
+                self.sites.push(access_pos);
+            }
+        }
+    }
+    fn visit_body(&self) -> bool {
+        true
+    }
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+impl std::fmt::Display for Sites {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "find sites of a term")
+    }
+}
+
+fn collect_atoms(e: &SExpr, atoms: &mut Vec<String>) {
+    match e.atom() {
+        Some(x) => atoms.push(x.to_string()),
+        None => e.children().iter().for_each(|x| collect_atoms(x, atoms)),
+    }
+}
+
+struct Rewriter<'a> {
+    content: &'a str,
+    name: &'a str,
+    args: &'a [NewArg],
+    /// Line and column of the name where the term is applied or defined.
+    sites: &'a HashSet<(usize, usize)>,
+    /// Atoms of the form,new names must not be one of them.
+    taken: Vec<String>,
+}
+
+impl<'a> Rewriter<'a> {
+    fn form(&self, f: &SExpr) -> String {
+        let children = f.children();
+        let modes: Vec<Option<Mode>> = match f.head() {
+            Some("rule") => {
+                // name and priority are atoms.
+                let first = children.iter().position(|x| x.atom().is_none());
+                children
+                    .iter()
+                    .enumerate()
+                    .map(|(index, _)| match first {
+                        Some(first) if index == first => Some(Mode::Pattern),
+                        Some(first) if index > first && index + 1 < children.len() => None,
+                        Some(first) if index > first => Some(Mode::Expr),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            Some("extractor") => vec![None, Some(Mode::ExtractorHead), Some(Mode::Pattern)],
+            Some("decl") => {
+                let name = children
+                    .iter()
+                    .position(|x| x.atom() == Some(self.name) && self.is_site(x));
+                children
+                    .iter()
+                    .enumerate()
+                    .map(|(index, _)| match name {
+                        Some(name) if index == name + 1 => Some(Mode::Signature),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            _ => return f.text(self.content).to_string(),
+        };
+        self.splice(f, |index, c| {
+            match (modes.get(index).cloned().flatten(), c) {
+                (Some(Mode::Signature), c) => self.signature(c.children()),
+                (Some(mode), c) => self.rewrite(c, mode),
+                // `(if-let pattern expr)` and `(if expr)`.
+                (None, c) if c.head() == Some("if-let") => {
+                    let mode = |index: usize| {
+                        if index == 1 {
+                            Mode::Pattern
+                        } else {
+                            Mode::Expr
+                        }
+                    };
+                    self.splice(c, |index, x| self.rewrite(x, mode(index)))
+                }
+                (None, c) if c.head() == Some("if") => {
+                    self.splice(c, |_, x| self.rewrite(x, Mode::Expr))
+                }
+                (None, c) => c.text(self.content).to_string(),
+            }
+        })
+    }
+
+    fn rewrite(&self, e: &SExpr, mode: Mode) -> String {
+        if e.atom().is_some() {
+            return e.text(self.content).to_string();
+        }
+        if e.head() == Some(self.name) && self.is_site(&e.children()[0]) {
+            let children = e.children();
+            let args: Vec<_> = children[1..]
+                .iter()
+                .map(|x| {
+                    let inner = match mode {
+                        Mode::ExtractorHead => Mode::Pattern,
+                        x => x,
+                    };
+                    self.rewrite(x, inner)
+                })
+                .collect();
+            let mut ret = format!("({}", self.name);
+            for a in self.new_args(&args, mode).into_iter() {
+                ret.push(' ');
+                ret.push_str(a.as_str());
+            }
+            ret.push(')');
+            return ret;
+        }
+        let inner = match mode {
+            Mode::ExtractorHead => return e.text(self.content).to_string(),
+            x => x,
+        };
+        self.splice(e, |_, x| self.rewrite(x, inner))
+    }
+
+    fn is_site(&self, e: &SExpr) -> bool {
+        self.sites.contains(&(e.start.line, e.start.col))
+    }
+
+    /// `(arg_ty...)` of the decl.
+    fn signature(&self, tys: &[SExpr]) -> String {
+        let tys: Vec<_> = tys
+            .iter()
+            .map(|x| x.text(self.content).to_string())
+            .collect();
+        format!("({})", self.new_args(&tys, Mode::Signature).join(" "))
+    }
+
+    fn new_args(&self, old: &[String], mode: Mode) -> Vec<String> {
+        let mut taken = self.taken.clone();
+        let mut fresh = |base: String| {
+            let mut name = base.clone();
+            let mut index = 1;
+            while taken.contains(&name) {
+                name = format!("{}{}", base, index);
+                index += 1;
+            }
+            taken.push(name.clone());
+            name
+        };
+        self.args
+            .iter()
+            .enumerate()
+            .map(|(index, a)| match a {
+                NewArg::Old(x) => old.get(*x).cloned().unwrap_or("_".to_string()),
+                NewArg::New(ty) => match mode {
+                    Mode::Pattern => "_".to_string(),
+                    Mode::Expr => PLACEHOLDER.to_string(),
+                    Mode::ExtractorHead => fresh(format!("arg{}", index)),
+                    Mode::Signature => ty.clone(),
+                },
+            })
+            .collect()
+    }
+
+    fn splice(&self, e: &SExpr, f: impl FnMut(usize, &SExpr) -> String) -> String {
+        splice(self.content, e, f)
+    }
+}
@@ -0,0 +1,278 @@
+use super::change_signature::decl_at;
+use super::context::Context;
+use super::converter::unresolved_terms;
+use super::extract_constructor::extract_constructor_actions;
+use super::extract_extractor::extract_extractor_actions;
+use super::inline::inline_actions;
+use super::item::PLACEHOLDER;
+use super::prio_fix::{is_conflict, prio_fixes};
+use super::project::*;
+use super::unused_var::unused_vars;
+use crate::sexpr::{parse_sexprs, pos_to_position, SExpr};
+use lsp_server::*;
+use lsp_types::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Handles code action request of the language server.
+pub fn on_code_action(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<CodeActionParams>(request.params.clone())
+        .expect("could not deserialize code action request");
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let mut actions = Vec::new();
+    unused_var_fixes(&context.project, &fpath, &parameters, &mut actions);
+    create_decl_fixes(&context.project, &fpath, &parameters, &mut actions);
+    change_signature_action(&context.project, &fpath, &parameters, &mut actions);
+    extract_extractor_actions(&context.project, &fpath, &parameters.range, &mut actions);
+    extract_constructor_actions(&context.project, &fpath, &parameters.range, &mut actions);
+    inline_actions(&context.project, &fpath, &parameters.range, &mut actions);
+    move_decl_action(&context.project, &fpath, &parameters, &mut actions);
+    // compiling is expensive,only when the client reports a conflict.
+    if parameters.context.diagnostics.iter().any(is_conflict) {
+        prio_fixes(&context.project, &fpath, &parameters.range, &mut actions);
+    }
+    let r = Response::new_ok(request.id.clone(), serde_json::to_value(actions).unwrap());
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
+
+/// Replace a unused variable with `_`,or remove `x @` of a unused bind.
+pub(crate) fn unused_var_fixes(
+    p: &Project,
+    fpath: &Path,
+    parameters: &CodeActionParams,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    let provider = match p.found_file_defs(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    for v in unused_vars(provider).into_iter() {
+        let loc = match p.mk_location(&v.var) {
+            Some(x) => x,
+            None => continue,
+        };
+        if !overlaps(&loc.range, &parameters.range) {
+            continue;
+        }
+        let (title, edit) = match v.subpat {
+            Some(subpat) => (
+                format!("Remove unused bind `{} @`", v.var.0),
+                TextEdit {
+                    range: Range {
+                        start: loc.range.start,
+                        end: pos_to_position(&subpat),
+                    },
+                    new_text: String::new(),
+                },
+            ),
+            None => (
+                format!("Replace `{}` with `_`", v.var.0),
+                TextEdit {
+                    range: loc.range,
+                    new_text: "_".to_string(),
+                },
+            ),
+        };
+        let diagnostics: Vec<_> = parameters
+            .context
+            .diagnostics
+            .iter()
+            .filter(|d| {
+                d.range == loc.range
+                    && d.code == Some(NumberOrString::String("unused-variable".to_string()))
+            })
+            .cloned()
+            .collect();
+        let mut changes = HashMap::new();
+        changes.insert(loc.uri.clone(), vec![edit]);
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title,
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: if !diagnostics.is_empty() {
+                Some(diagnostics)
+            } else {
+                None
+            },
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            }),
+            is_preferred: Some(true),
+            ..Default::default()
+        }));
+    }
+}
+
+/// Declare a term applied but not declared,
+/// next to the decl of the rule's root term if it is in the same file.
+pub(crate) fn create_decl_fixes(
+    p: &Project,
+    fpath: &Path,
+    parameters: &CodeActionParams,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    let content = match p.file_text(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).unwrap_or_default();
+    let mut created = Vec::new();
+    for t in unresolved_terms(p, fpath).into_iter() {
+        let loc = match p.mk_location(&t.sym) {
+            Some(x) => x,
+            None => continue,
+        };
+        if !overlaps(&loc.range, &parameters.range) || created.contains(&t.sym.0) {
+            continue;
+        }
+        created.push(t.sym.0.clone());
+        let decl = format!(
+            "(decl {} ({}) {})",
+            t.sym.0,
+            t.arg_tys
+                .iter()
+                .map(|x| type_name(x.as_ref()))
+                .collect::<Vec<_>>()
+                .join(" "),
+            type_name(t.ret_ty.as_ref())
+        );
+        let root_decl = t
+            .root
+            .as_ref()
+            .and_then(|root| forms.iter().find(|f| decl_name(f) == Some(root.as_str())));
+        let edit = match root_decl {
+            Some(f) => {
+                let end = pos_to_position(&f.end);
+                TextEdit {
+                    range: Range { start: end, end },
+                    new_text: format!("\n{}", decl),
+                }
+            }
+            None => {
+                let start = match forms.iter().find(|f| f.contains_pos(&t.sym.1)) {
+                    Some(f) => Position::new(f.start_line(), 0),
+                    None => continue,
+                };
+                TextEdit {
+                    range: Range { start, end: start },
+                    new_text: format!("{}\n", decl),
+                }
+            }
+        };
+        let mut changes = HashMap::new();
+        changes.insert(loc.uri.clone(), vec![edit]);
+        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+            title: format!("Create `{}`", decl),
+            kind: Some(CodeActionKind::QUICKFIX),
+            edit: Some(WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }));
+    }
+}
+
+/// Name of a type,`PLACEHOLDER` if it can't be inferred.
+fn type_name(x: Option<&String>) -> &str {
+    x.map(|x| x.as_str()).unwrap_or(PLACEHOLDER)
+}
+
+/// Change the signature of the decl at the cursor,
+/// the new signature is asked by the client command.
+fn change_signature_action(
+    p: &Project,
+    fpath: &Path,
+    parameters: &CodeActionParams,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    let pos = parameters.range.start;
+    let decl = match decl_at(p, fpath, pos.line, pos.character) {
+        Some(x) => x,
+        None => return,
+    };
+    let title = format!("Change signature of `{}`", decl.term.0);
+    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.clone(),
+        kind: Some(CodeActionKind::REFACTOR),
+        command: Some(Command {
+            title,
+            command: "isle-analyzer.isle.change.signature".to_string(),
+            arguments: Some(vec![serde_json::json!({
+                "fpath": fpath.to_str().unwrap(),
+                "line": pos.line,
+                "col": pos.character,
+                "name": decl.term.0,
+                "arg_tys": decl.arg_tys.iter().map(|x| x.0.as_str()).collect::<Vec<_>>(),
+            })]),
+        }),
+        ..Default::default()
+    }));
+}
+
+/// Move the decl at the cursor to another file,the file is asked by the client command.
+fn move_decl_action(
+    p: &Project,
+    fpath: &Path,
+    parameters: &CodeActionParams,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    let content = match p.file_text(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).unwrap_or_default();
+    let line = parameters.range.start.line;
+    let form = match forms
+        .iter()
+        .find(|f| f.start_line() <= line && line <= f.end_line())
+    {
+        Some(x) => x,
+        None => return,
+    };
+    let name = match decl_name(form) {
+        Some(x) => x,
+        None => return,
+    };
+    // the server finds the decl by the position of its name.
+    let pos = match form.children().iter().find(|x| x.atom() == Some(name)) {
+        Some(x) => pos_to_position(&x.start),
+        None => return,
+    };
+    let title = format!("Move `{}` to another file", name);
+    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.clone(),
+        kind: Some(CodeActionKind::REFACTOR),
+        command: Some(Command {
+            title,
+            command: "isle-analyzer.isle.move.decl".to_string(),
+            arguments: Some(vec![serde_json::json!({
+                "fpath": fpath.to_str().unwrap(),
+                "line": pos.line,
+                "col": pos.character,
+                "name": name,
+            })]),
+        }),
+        ..Default::default()
+    }));
+}
+
+/// `foo` of `(decl pure foo ...)`.
+pub(crate) fn decl_name(f: &SExpr) -> Option<&str> {
+    if f.head() != Some("decl") {
+        return None;
+    }
+    f.children()
+        .iter()
+        .skip(1)
+        .filter_map(|x| x.atom())
+        .find(|x| !["pure", "multi", "partial"].contains(x))
+}
+
+pub(crate) fn overlaps(a: &Range, b: &Range) -> bool {
+    a.start <= b.end && b.start <= a.end
+}
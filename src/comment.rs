@@ -29,6 +29,8 @@ impl CommentExtrator {
         const SEMI_COLON: u8 = 59;
         let mut comments = Vec::new();
         let mut comment = Vec::new();
+        // some code before the comment on this line.
+        let mut after_code = false;
         let last_index = content.as_bytes().len() - 1;
         for (index, c) in content.as_bytes().iter().enumerate() {
             match state {
@@ -36,12 +38,14 @@ impl CommentExtrator {
                     NEW_LINE => {
                         line += 1;
                         col = 0;
+                        after_code = false;
                     }
                     SEMI_COLON => {
                         state = State::OneSemiColon;
                         col += 1;
                     }
                     _ => {
+                        after_code = after_code || !c.is_ascii_whitespace();
                         col += 1;
                     }
                 },
@@ -50,6 +54,7 @@ impl CommentExtrator {
                         state = State::Comment;
                     } else {
                         state = State::Init;
+                        after_code = true;
                     }
                     col += 1;
                 }
@@ -64,9 +69,11 @@ impl CommentExtrator {
                             line,
                             col: col_,
                             content: String::from_utf8(comment.clone()).unwrap(),
+                            after_code,
                         });
                         line += 1;
                         col = 0;
+                        after_code = false;
                         comment = Vec::new();
                         state = State::Init;
                     } else if *c == SEMI_COLON {
@@ -81,6 +88,10 @@ impl CommentExtrator {
         }
         Self { comments }
     }
+
+    pub(crate) fn comments(&self) -> &Vec<Comment> {
+        &self.comments
+    }
 }
 
 pub struct Comment {
@@ -88,10 +99,14 @@ pub struct Comment {
     #[allow(dead_code)]
     pub(crate) col: u32,
     pub(crate) content: String,
+    /// Like `(rule ...) ;; comment`.
+    pub(crate) after_code: bool,
 }
 
 pub struct DocumentComments {
     comments: HashMap<Pos, String>,
+    /// First line of the comments right above a definition,zero-based.
+    attached: HashMap<Pos, u32>,
 }
 
 impl DocumentComments {
@@ -132,7 +147,7 @@ impl DocumentComments {
                 Ordering::Less
             }
         });
-        let mut tmp = Vec::new();
+        let mut tmp: Vec<&Comment> = Vec::new();
         fn make_document_symbol_comment(x: &Vec<&Comment>) -> String {
             let mut ret = String::new();
             for (index, c) in x.iter().enumerate() {
@@ -156,8 +171,19 @@ impl DocumentComments {
             ret
         }
         let mut comments = HashMap::new();
+        let mut attached = HashMap::new();
         s.iter().for_each(|x| match x {
             PosOrComment::Pos(x) => {
+                let mut line = (x.line as u32) - 1;
+                for c in tmp.iter().rev() {
+                    if c.after_code || c.line + 1 != line {
+                        break;
+                    }
+                    line = c.line;
+                }
+                if line + 1 != x.line as u32 {
+                    attached.insert(*x, line);
+                }
                 comments.insert(x.clone(), make_document_symbol_comment(&tmp));
                 tmp = Vec::new();
             }
@@ -165,12 +191,17 @@ impl DocumentComments {
                 tmp.push(*s);
             }
         });
-        Self { comments }
+        Self { comments, attached }
     }
 
     pub(crate) fn get_comment(&self, p: &Pos) -> Option<&String> {
         self.comments.get(p)
     }
+
+    /// Zero-based first line of the comments right above the definition at `p`.
+    pub(crate) fn attached_start(&self, p: &Pos) -> Option<u32> {
+        self.attached.get(p).cloned()
+    }
 }
 
 #[test]
@@ -292,6 +292,8 @@ fn item_to_completion_item(item: &Item) -> Option<CompletionItem> {
             kind: Some(CompletionItemKind::ENUM_MEMBER),
             ..Default::default()
         },
+        Item::ConstInt { .. } => return None,
+        Item::ExternFunc { .. } => return None,
     };
     Some(x)
 }
@@ -340,7 +342,8 @@ impl VisitContext {
     pub(crate) fn decl_(&self, mut call_back: impl FnMut(&Decl), x: u8) {
         self.all_top_items(|i| match i {
             Item::Decl { decl, kind } => {
-                if kind.has(x) {
+                // not implemented yet,could be used as both.
+                if kind.0 == 0 || kind.has(x) {
                     call_back(decl);
                 }
             }
@@ -0,0 +1,57 @@
+use super::lint::LintLevel;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Settings sent by the client in `initializationOptions`
+/// or `workspace/didChangeConfiguration`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ServerConfig {
+    /// Directories contains the Rust code implement `extern` functions.
+    /// Directories of the `ISLE` files are used if empty.
+    pub rust_dirs: Vec<PathBuf>,
+    pub inlay_hints: InlayHintsConfig,
+    /// Level of lints by name,overrides the default level of the lint.
+    pub lints: HashMap<String, LintLevel>,
+}
+
+/// Which kind of inlay hints are shown.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct InlayHintsConfig {
+    /// Type of variables not declared.
+    pub variable_types: bool,
+    /// Converters `ISLE` inserted.
+    pub implicit_conversions: bool,
+    /// Parameter type before each argument of a term.
+    pub argument_types: bool,
+    /// Return type after a term.
+    pub return_types: bool,
+    /// Priority of rules without a explicit one.
+    pub rule_priorities: bool,
+}
+
+impl Default for InlayHintsConfig {
+    fn default() -> Self {
+        Self {
+            variable_types: true,
+            implicit_conversions: true,
+            argument_types: false,
+            return_types: false,
+            rule_priorities: false,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn from_value(x: Option<&serde_json::Value>) -> Self {
+        match x {
+            Some(x) if !x.is_null() => serde_json::from_value(x.clone()).unwrap_or_else(|err| {
+                log::error!("invalid configuration:{:?}", err);
+                Self::default()
+            }),
+            _ => Self::default(),
+        }
+    }
+}
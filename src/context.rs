@@ -1,4 +1,6 @@
+use super::config::ServerConfig;
 use super::project::Project;
+use super::semantic_tokens::SemanticTokensCache;
 use lsp_server::*;
 
 pub struct Context {
@@ -6,4 +8,8 @@ pub struct Context {
     pub connection: Connection,
     /// loaded project.
     pub project: Project,
+    /// settings from the client.
+    pub config: ServerConfig,
+    /// semantic tokens sent to client.
+    pub semantic_tokens: SemanticTokensCache,
 }
@@ -0,0 +1,263 @@
+//! Find where `ISLE` would insert a converter declared by `(convert Inner Outer term)`.
+//! Like `sema`,a conversion applies when the type of a term or a variable
+//! is not the type expected by the enclosing term.
+
+use super::item::*;
+use super::project::*;
+use super::utils::{expr_pos, pattern_pos};
+use cranelift_isle::ast::*;
+use cranelift_isle::lexer::Pos;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub(crate) struct Conversion {
+    /// Start of the converted pattern or expression.
+    pub(crate) pos: Pos,
+    /// The converter term.
+    pub(crate) term: Ident,
+    pub(crate) inner: String,
+    pub(crate) outer: String,
+}
+
+/// All implicit conversions in rules of `fpath`.
+pub(crate) fn implicit_conversions(p: &Project, fpath: &Path) -> Vec<Conversion> {
+    let mut has_converter = false;
+    ProjectAstProvider::new(p).with_converter(|_| has_converter = true);
+    if !has_converter {
+        return vec![];
+    }
+    walk_file(p, fpath).results
+}
+
+/// Type expected at the start of each pattern and expression in rules of `fpath`,if known.
+pub(crate) fn expected_types(p: &Project, fpath: &Path) -> Vec<(Pos, String)> {
+    walk_file(p, fpath).types
+}
+
+/// Terms applied in rules of `fpath` but not declared anywhere.
+pub(crate) fn unresolved_terms(p: &Project, fpath: &Path) -> Vec<UnresolvedTerm> {
+    walk_file(p, fpath).unresolved
+}
+
+pub(crate) struct UnresolvedTerm {
+    pub(crate) sym: Ident,
+    /// Types of the arguments if known.
+    pub(crate) arg_tys: Vec<Option<String>>,
+    /// Type expected by the enclosing term if known.
+    pub(crate) ret_ty: Option<String>,
+    /// The root term of the rule.
+    pub(crate) root: Option<String>,
+}
+
+fn walk_file<'a>(p: &'a Project, fpath: &Path) -> Walker<'a> {
+    let mut converters = HashMap::new();
+    ProjectAstProvider::new(p).with_converter(|c| {
+        converters.insert((c.inner_ty.0.clone(), c.outer_ty.0.clone()), c.term.clone());
+    });
+    let mut w = Walker {
+        p,
+        converters,
+        vars: HashMap::new(),
+        root: None,
+        results: vec![],
+        unresolved: vec![],
+        types: vec![],
+    };
+    if let Some(provider) = p.found_file_defs(fpath) {
+        provider.with_rule(|r| w.walk_rule(r));
+    }
+    w
+}
+
+/// Type of the term and types of its arguments,
+/// `sym` may be a enum variant like `Type.Variant`.
+pub(crate) fn term_types(p: &Project, sym: &Ident) -> Option<(String, Vec<String>)> {
+    match SplitedSymbol::from(sym) {
+        SplitedSymbol::One(_) => match p.context.query_item_clone(&sym.0) {
+            Item::Decl { decl, .. } => Some((
+                decl.ret_ty.0,
+                decl.arg_tys.into_iter().map(|x| x.0).collect(),
+            )),
+            _ => None,
+        },
+        SplitedSymbol::Two([x, y]) => match p.context.query_item_clone(&x.symbol) {
+            Item::Type { ty } => match &ty.ty {
+                TypeValue::Primitive(_, _) => None,
+                TypeValue::Enum(vs, _) => vs.iter().find(|v| v.name.0 == y.symbol).map(|v| {
+                    (
+                        ty.name.0.clone(),
+                        v.fields.iter().map(|f| f.ty.0.clone()).collect(),
+                    )
+                }),
+            },
+            _ => None,
+        },
+    }
+}
+
+struct Walker<'a> {
+    p: &'a Project,
+    /// (inner,outer) -> term
+    converters: HashMap<(String, String), Ident>,
+    /// Type of variables bound in current rule.
+    vars: HashMap<String, String>,
+    /// Root term of current rule.
+    root: Option<String>,
+    results: Vec<Conversion>,
+    unresolved: Vec<UnresolvedTerm>,
+    types: Vec<(Pos, String)>,
+}
+
+impl<'a> Walker<'a> {
+    fn walk_rule(&mut self, r: &Rule) {
+        self.vars.clear();
+        self.root = get_rule_target(&r.pattern).map(|(name, _)| name.clone());
+        let ret_ty = get_rule_target(&r.pattern).and_then(|(name, _)| {
+            match self.p.context.query_item_clone(name) {
+                Item::Decl { decl, .. } => Some(decl.ret_ty.0),
+                _ => None,
+            }
+        });
+        self.walk_pattern(&r.pattern, ret_ty.as_ref());
+        for i in r.iflets.iter() {
+            let ty = self.walk_expr(&i.expr, None);
+            self.walk_pattern(&i.pattern, ty.as_ref());
+        }
+        self.walk_expr(&r.expr, ret_ty.as_ref());
+    }
+
+    /// Record `sym` if it is not declared.
+    fn unresolved(&mut self, sym: &Ident, arg_tys: Vec<Option<String>>, expected: Option<&String>) {
+        match SplitedSymbol::from(sym) {
+            SplitedSymbol::One(_) => {}
+            SplitedSymbol::Two(_) => return,
+        }
+        match self.p.context.query_item_clone(&sym.0) {
+            Item::Dummy => {}
+            _ => return,
+        }
+        self.unresolved.push(UnresolvedTerm {
+            sym: sym.clone(),
+            arg_tys,
+            ret_ty: expected.cloned(),
+            root: self.root.clone(),
+        });
+    }
+
+    /// Type of a argument of a extractor,known if it is bound or a term.
+    fn pattern_ty(&self, pat: &Pattern) -> Option<String> {
+        match pat {
+            Pattern::Var { var, .. } => self.vars.get(&var.0).cloned(),
+            Pattern::BindPattern { subpat, .. } => self.pattern_ty(subpat.as_ref()),
+            Pattern::Term { sym, .. } => term_types(self.p, sym).map(|(x, _)| x),
+            _ => None,
+        }
+    }
+
+    /// Record a conversion if `actual` is not `expected`,
+    /// the converter must be a extractor in patterns and a constructor in expressions.
+    fn convert(&mut self, pos: Pos, actual: &String, expected: Option<&String>, kind: u8) {
+        let expected = match expected {
+            Some(x) => x,
+            None => return,
+        };
+        if actual == expected {
+            return;
+        }
+        if let Some(term) = self.converters.get(&(actual.clone(), expected.clone())) {
+            match self.p.context.query_item_clone(&term.0) {
+                Item::Decl { kind: k, .. } if k.has(kind) => {}
+                _ => return,
+            }
+            self.results.push(Conversion {
+                pos,
+                term: term.clone(),
+                inner: actual.clone(),
+                outer: expected.clone(),
+            });
+        }
+    }
+
+    fn walk_pattern(&mut self, pat: &Pattern, expected: Option<&String>) {
+        if let Some(ty) = expected {
+            self.types.push((pattern_pos(pat), ty.clone()));
+        }
+        match pat {
+            Pattern::Var { var, .. } => {
+                if !self.vars.contains_key(&var.0) {
+                    if let Some(ty) = expected {
+                        self.vars.insert(var.0.clone(), ty.clone());
+                    }
+                }
+            }
+            Pattern::BindPattern { var, subpat, .. } => {
+                if let Some(ty) = expected {
+                    self.vars.insert(var.0.clone(), ty.clone());
+                }
+                self.walk_pattern(subpat.as_ref(), expected);
+            }
+            Pattern::Term { sym, args, pos } => {
+                let (ret_ty, arg_tys) = match term_types(self.p, sym) {
+                    Some(x) => x,
+                    None => {
+                        let tys = args.iter().map(|a| self.pattern_ty(a)).collect();
+                        self.unresolved(sym, tys, expected);
+                        (String::new(), vec![])
+                    }
+                };
+                if !ret_ty.is_empty() {
+                    self.convert(*pos, &ret_ty, expected, DeclKind::EXTRATOR);
+                }
+                for (index, a) in args.iter().enumerate() {
+                    self.walk_pattern(a, arg_tys.get(index));
+                }
+            }
+            Pattern::And { subpats, .. } => {
+                for s in subpats.iter() {
+                    self.walk_pattern(s, expected);
+                }
+            }
+            Pattern::Wildcard { .. }
+            | Pattern::ConstInt { .. }
+            | Pattern::ConstPrim { .. }
+            | Pattern::MacroArg { .. } => {}
+        }
+    }
+
+    /// Walk `e` and return the type of it.
+    fn walk_expr(&mut self, e: &Expr, expected: Option<&String>) -> Option<String> {
+        if let Some(ty) = expected {
+            self.types.push((expr_pos(e), ty.clone()));
+        }
+        match e {
+            Expr::Term { sym, args, pos } => {
+                let (ret_ty, arg_tys) = match term_types(self.p, sym) {
+                    Some(x) => x,
+                    None => {
+                        let tys = args.iter().map(|a| self.walk_expr(a, None)).collect();
+                        self.unresolved(sym, tys, expected);
+                        return None;
+                    }
+                };
+                self.convert(*pos, &ret_ty, expected, DeclKind::CONSTRUCTOR);
+                for (index, a) in args.iter().enumerate() {
+                    self.walk_expr(a, arg_tys.get(index));
+                }
+                Some(ret_ty)
+            }
+            Expr::Var { name, pos } => {
+                let ty = self.vars.get(&name.0)?.clone();
+                self.convert(*pos, &ty, expected, DeclKind::CONSTRUCTOR);
+                Some(ty)
+            }
+            Expr::ConstInt { .. } | Expr::ConstPrim { .. } => None,
+            Expr::Let { defs, body, .. } => {
+                for d in defs.iter() {
+                    self.walk_expr(d.val.as_ref(), Some(&d.ty.0));
+                    self.vars.insert(d.var.0.clone(), d.ty.0.clone());
+                }
+                self.walk_expr(body.as_ref(), expected)
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Top-level items nothing refers to.
+//! Like decls with no rules and no uses,extern constants and enum variants never used.
+//! Rules of a term implement it rather than use it,
+//! a term with rules is only alive when called by another term or from Rust.
+
+use super::context::Context;
+use super::item::*;
+use super::lint::*;
+use super::project::*;
+use super::rust_scan::{find_fn_refs, parent_dirs, rust_files, FnRefKind};
+use cranelift_isle::ast::{Extern, Ident, Pattern, TypeValue};
+use cranelift_isle::lexer::Pos;
+use lsp_server::*;
+use lsp_types::*;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeadCodeKind {
+    Decl,
+    Type,
+    Const,
+    Variant,
+}
+
+impl DeadCodeKind {
+    fn to_static_str(self) -> &'static str {
+        match self {
+            DeadCodeKind::Decl => "decl",
+            DeadCodeKind::Type => "type",
+            DeadCodeKind::Const => "const",
+            DeadCodeKind::Variant => "enum variant",
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct DeadCode {
+    pub kind: DeadCodeKind,
+    pub name: String,
+    pub location: Location,
+}
+
+/// All unused top-level items of `p`,sorted by location.
+pub fn dead_code(p: &Project) -> Vec<DeadCode> {
+    let provider = ProjectAstProvider::new(p);
+    let mut handler = Handler::default();
+    provider.with_rule(|r| {
+        if let Pattern::Term { sym, .. } = &r.pattern {
+            handler.roots.insert(sym.1);
+        }
+    });
+    provider.with_extractor(|e| {
+        handler.roots.insert(e.term.1);
+    });
+    p.run_full_visitor(&mut handler);
+    // converters are applied by `ISLE` implicitly.
+    provider.with_converter(|c| {
+        if let Item::Decl { decl, .. } = p.context.query_item_clone(&c.term.0) {
+            handler.used.insert(decl.term.1);
+        }
+    });
+    // the generated `constructor_xxx` of a term with rules may be the entry called by Rust.
+    let mut entries = Vec::new();
+    provider.with_decl(|d| {
+        if handler.implemented.contains(&d.term.1) && !handler.used.contains(&d.term.1) {
+            entries.push(d.term.clone());
+        }
+    });
+    for x in called_from_rust(p, &entries) {
+        handler.used.insert(x);
+    }
+    let mut ret = Vec::new();
+    let mut push = |kind: DeadCodeKind, name: &Ident, len: u32| {
+        if handler.used.contains(&name.1) {
+            return;
+        }
+        if let Some(location) = p.mk_location(&(name.1, len)) {
+            ret.push(DeadCode {
+                kind,
+                name: name.0.clone(),
+                location,
+            });
+        }
+    };
+    provider.with_decl(|d| push(DeadCodeKind::Decl, &d.term, d.term.0.len() as u32));
+    provider.with_type(|t| {
+        push(DeadCodeKind::Type, &t.name, t.name.0.len() as u32);
+        match &t.ty {
+            TypeValue::Primitive(_, _) => {}
+            // variants of a extern type may be used by Rust code.
+            TypeValue::Enum(_, _) if t.is_extern => {}
+            TypeValue::Enum(variants, _) => {
+                for v in variants.iter() {
+                    push(DeadCodeKind::Variant, &v.name, v.name.0.len() as u32);
+                }
+            }
+        }
+    });
+    provider.with_extern(|x| match x {
+        // `name` not include the leading `$`.
+        Extern::Const { name, .. } => push(DeadCodeKind::Const, name, name.0.len() as u32 + 1),
+        Extern::Extractor { .. } | Extern::Constructor { .. } => {}
+    });
+    ret.sort_by(|a, b| {
+        (a.location.uri.as_str(), a.location.range.start)
+            .cmp(&(b.location.uri.as_str(), b.location.range.start))
+    });
+    ret
+}
+
+/// Positions of `terms` whose `constructor_xxx` is called in Rust files
+/// near the `ISLE` files of `p`.
+fn called_from_rust(p: &Project, terms: &[Ident]) -> Vec<Pos> {
+    if terms.is_empty() {
+        return vec![];
+    }
+    let files: Vec<_> = p
+        .get_filenames()
+        .iter()
+        .map(|x| PathBuf::from(x.as_ref()))
+        .collect();
+    let mut ret = Vec::new();
+    for f in rust_files(&parent_dirs(&files)).into_iter() {
+        let content = match std::fs::read_to_string(&f) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        for t in terms.iter() {
+            if find_fn_refs(content.as_str(), &format!("constructor_{}", t.0))
+                .iter()
+                .any(|x| x.1 == FnRefKind::Call)
+            {
+                ret.push(t.1);
+            }
+        }
+    }
+    ret
+}
+
+/// Definition positions of everything accessed.
+#[derive(Default)]
+struct Handler {
+    used: HashSet<Pos>,
+    /// Positions of rule roots and extractor heads,they implement a term.
+    roots: HashSet<Pos>,
+    /// Terms with rules or a extractor.
+    implemented: HashSet<Pos>,
+    /// The term whose rule or extractor is visiting.
+    current: Option<Pos>,
+}
+
+impl ItemOrAccessHandler for Handler {
+    fn handle_item_or_access(&mut self, _p: &Project, item: &ItemOrAccess) {
+        match item {
+            // a new file.
+            ItemOrAccess::Item(Item::Decl { .. }) => self.current = None,
+            ItemOrAccess::Item(_) => {}
+            ItemOrAccess::Access(access) => {
+                let (access_pos, def, _) = access.access_def_loc();
+                match access.kind {
+                    // binding a Rust function is not a use.
+                    AccessKind::DeclExtern => {}
+                    _ if self.roots.contains(&access_pos) => {
+                        self.implemented.insert(def);
+                        self.current = Some(def);
+                    }
+                    // a recursive call is not a use either.
+                    _ if self.current == Some(def) => {}
+                    _ => {
+                        self.used.insert(def);
+                    }
+                }
+            }
+        }
+    }
+    fn visit_body(&self) -> bool {
+        true
+    }
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+impl std::fmt::Display for Handler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "find dead code")
+    }
+}
+
+/// Report unused items with `DiagnosticTag::UNNECESSARY`.
+pub struct UnusedItems;
+
+impl Lint for UnusedItems {
+    fn name(&self) -> &'static str {
+        "unused-item"
+    }
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Hint
+    }
+    fn check(&self, p: &Project, results: &mut Vec<LintResult>) {
+        for d in dead_code(p).into_iter() {
+            results.push(LintResult {
+                message: format!("{} `{}` is never used", d.kind.to_static_str(), d.name),
+                loc: d.location,
+                tags: vec![DiagnosticTag::UNNECESSARY],
+            });
+        }
+    }
+}
+
+/// Handles `isle/dead_code_report`,all unused items of the project.
+pub fn on_dead_code_report(context: &Context, request: &Request) {
+    let r = Response::new_ok(
+        request.id.clone(),
+        serde_json::to_value(dead_code(&context.project)).unwrap(),
+    );
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
@@ -0,0 +1,60 @@
+use super::context::*;
+use super::goto_definition;
+use super::item::*;
+use super::project::Project;
+use super::references;
+use lsp_server::*;
+use lsp_types::*;
+
+/// Handles document highlight request of the language server.
+pub fn on_document_highlight_request(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<DocumentHighlightParams>(request.params.clone())
+        .expect("could not deserialize document highlight request");
+    let fpath = parameters.text_document_position_params.text_document.uri;
+    let loc = parameters.text_document_position_params.position;
+    let line = loc.line;
+    let col = loc.character;
+    log::info!(
+        "request is document highlight,fpath:{:?}  line:{} col:{}",
+        fpath,
+        line,
+        col,
+    );
+    let highlights = document_highlights(&context.project, &fpath, line, col);
+    let r = Response::new_ok(
+        request.id.clone(),
+        serde_json::to_value(Some(highlights)).unwrap(),
+    );
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
+
+/// Highlights of the item at the position,restricted to file `fpath`.
+pub(crate) fn document_highlights(
+    p: &Project,
+    fpath: &Url,
+    line: u32,
+    col: u32,
+) -> Vec<DocumentHighlight> {
+    let mut goto_definition = goto_definition::Handler::new(fpath.clone(), line, col);
+    p.run_visitor_for_file(&fpath.to_file_path().unwrap(), &mut goto_definition);
+    let def = match goto_definition.result_item_or_access {
+        Some(x) => match x {
+            ItemOrAccess::Item(d) => d,
+            ItemOrAccess::Access(Access { def, .. }) => def,
+        },
+        None => Item::Dummy,
+    };
+    match &def {
+        // All unresolved names share the same location.
+        Item::Dummy => vec![],
+        _ => {
+            let mut handle = references::Handler::new(def.def_loc(), true);
+            p.run_visitor_for_file(&fpath.to_file_path().unwrap(), &mut handle);
+            handle.to_document_highlights(p, fpath, &def)
+        }
+    }
+}
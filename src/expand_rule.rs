@@ -0,0 +1,212 @@
+//! Show a rule as the compiler sees it,
+//! extractor macros are expanded,implicit conversions are inserted,
+//! enum variants are fully qualified and variables are annotated with their types.
+
+use super::context::Context;
+use super::prio_fix::rule_range;
+use super::project::*;
+use crate::send_err;
+use crate::sexpr::parse_sexprs;
+use cranelift_isle::ast::Def;
+use cranelift_isle::lexer::Pos;
+use cranelift_isle::sema::{self, TermEnv, TypeEnv};
+use lsp_server::*;
+use lsp_types::*;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Clone, serde::Deserialize)]
+struct Req {
+    fpath: String,
+    line: u32,
+    col: u32,
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct ExpandedRule {
+    /// The rule expanded.
+    pub(crate) rule: Location,
+    pub(crate) text: String,
+}
+
+/// Handles `isle/expand_rule`,expand the rule at the position.
+pub fn on_expand_rule(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<Req>(request.params.clone())
+        .expect("could not deserialize expand rule request");
+    let fpath = PathBuf::from_str(parameters.fpath.as_str()).unwrap();
+    let pos = Position::new(parameters.line, parameters.col);
+    match expand_rule(&context.project, &fpath, pos) {
+        Ok(x) => {
+            let r = Response::new_ok(request.id.clone(), serde_json::to_value(x).unwrap());
+            context
+                .connection
+                .sender
+                .send(Message::Response(r))
+                .unwrap();
+        }
+        Err(err) => send_err(context, err, request.id.clone()),
+    }
+}
+
+pub(crate) fn expand_rule(
+    p: &Project,
+    fpath: &Path,
+    pos: Position,
+) -> Result<ExpandedRule, String> {
+    let not_found = || "No rule found".to_string();
+    let file_index = p.found_file_index(fpath).ok_or_else(not_found)?;
+    let content = p.file_text(fpath).ok_or_else(not_found)?;
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap())
+        .map_err(|err| format!("parse {:?} failed:{:?}", fpath, err))?;
+    let form = forms
+        .iter()
+        .find(|f| {
+            let r = f.range();
+            r.start <= pos && pos <= r.end
+        })
+        .ok_or_else(not_found)?;
+    let rule_pos = p
+        .defs
+        .defs
+        .iter()
+        .find_map(|d| match d {
+            Def::Rule(r) if r.pos.file == file_index && form.contains_pos(&r.pos) => Some(r.pos),
+            _ => None,
+        })
+        .ok_or_else(not_found)?;
+    let sema = p
+        .sema()
+        .ok_or_else(|| "The project does not type check".to_string())?;
+    let (tyenv, termenv) = (&sema.tyenv, &sema.termenv);
+    let rule = termenv
+        .rules
+        .iter()
+        .find(|r| same_pos(&r.pos, &rule_pos))
+        .ok_or_else(not_found)?;
+    let printer = Printer {
+        tyenv,
+        termenv,
+        rule,
+    };
+    Ok(ExpandedRule {
+        rule: Location {
+            uri: Url::from_file_path(fpath).unwrap(),
+            range: rule_range(p, &rule_pos).unwrap_or_default(),
+        },
+        text: printer.rule(),
+    })
+}
+
+fn same_pos(a: &Pos, b: &Pos) -> bool {
+    (a.file, a.line, a.col) == (b.file, b.line, b.col)
+}
+
+struct Printer<'a> {
+    tyenv: &'a TypeEnv,
+    termenv: &'a TermEnv,
+    rule: &'a sema::Rule,
+}
+
+impl<'a> Printer<'a> {
+    fn sym(&self, s: sema::Sym) -> &str {
+        self.tyenv.syms[s.index()].as_str()
+    }
+
+    fn ty(&self, ty: sema::TypeId) -> &str {
+        self.tyenv.types[ty.index()].name(self.tyenv)
+    }
+
+    fn term(&self, term: sema::TermId) -> &str {
+        self.sym(self.termenv.terms[term.index()].name)
+    }
+
+    /// `name:Type` of a variable.
+    fn var(&self, var: sema::VarId) -> String {
+        match self.rule.vars.iter().find(|x| x.id == var) {
+            Some(x) => format!("{}:{}", self.sym(x.name), self.ty(x.ty)),
+            None => format!("v{}", var.index()),
+        }
+    }
+
+    fn rule(&self) -> String {
+        let r = self.rule;
+        let mut ret = format!("(rule {} ({}", r.prio, self.term(r.root_term));
+        for a in r.args.iter() {
+            ret.push(' ');
+            ret.push_str(self.pattern(a).as_str());
+        }
+        ret.push(')');
+        for i in r.iflets.iter() {
+            ret.push_str(
+                format!(
+                    "\n  (if-let {} {})",
+                    self.pattern(&i.lhs),
+                    self.expr(&i.rhs)
+                )
+                .as_str(),
+            );
+        }
+        ret.push_str(format!("\n  {})\n", self.expr(&r.rhs)).as_str());
+        ret
+    }
+
+    fn pattern(&self, pat: &sema::Pattern) -> String {
+        match pat {
+            // a variable is bound to `_` when it first appears.
+            sema::Pattern::BindPattern(_, var, subpat) => match subpat.as_ref() {
+                sema::Pattern::Wildcard(_) => self.var(*var),
+                subpat => format!("{} @ {}", self.var(*var), self.pattern(subpat)),
+            },
+            sema::Pattern::Var(_, var) => self.var(*var),
+            sema::Pattern::ConstInt(_, val) => val.to_string(),
+            sema::Pattern::ConstPrim(_, val) => format!("${}", self.sym(*val)),
+            sema::Pattern::Term(_, term, args) => {
+                let mut ret = format!("({}", self.term(*term));
+                for a in args.iter() {
+                    ret.push(' ');
+                    ret.push_str(self.pattern(a).as_str());
+                }
+                ret.push(')');
+                ret
+            }
+            sema::Pattern::Wildcard(_) => "_".to_string(),
+            sema::Pattern::And(_, subpats) => {
+                let mut ret = "(and".to_string();
+                for a in subpats.iter() {
+                    ret.push(' ');
+                    ret.push_str(self.pattern(a).as_str());
+                }
+                ret.push(')');
+                ret
+            }
+        }
+    }
+
+    fn expr(&self, e: &sema::Expr) -> String {
+        match e {
+            sema::Expr::Term(_, term, args) => {
+                let mut ret = format!("({}", self.term(*term));
+                for a in args.iter() {
+                    ret.push(' ');
+                    ret.push_str(self.expr(a).as_str());
+                }
+                ret.push(')');
+                ret
+            }
+            sema::Expr::Var(_, var) => self.var(*var),
+            sema::Expr::ConstInt(_, val) => val.to_string(),
+            sema::Expr::ConstPrim(_, val) => format!("${}", self.sym(*val)),
+            sema::Expr::Let { bindings, body, .. } => {
+                let bindings: Vec<_> = bindings
+                    .iter()
+                    .map(|(var, _, val)| format!("({} {})", self.var(*var), self.expr(val)))
+                    .collect();
+                format!(
+                    "(let ({}) {})",
+                    bindings.join(" "),
+                    self.expr(body.as_ref())
+                )
+            }
+        }
+    }
+}
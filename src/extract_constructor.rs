@@ -0,0 +1,228 @@
+//! Extract a expression of a rule into a new constructor,
+//! free variables of the expression become arguments of the constructor.
+
+use super::converter::{expected_types, term_types};
+use super::extract_extractor::new_name;
+use super::item::*;
+use super::project::*;
+use super::utils::expr_pos;
+use crate::sexpr::{enclosing, parse_sexprs, position_to_offset};
+use cranelift_isle::ast::{Expr, Ident};
+use lsp_types::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Extract the expression selected by `range` into a constructor.
+pub(crate) fn extract_constructor_actions(
+    p: &Project,
+    fpath: &Path,
+    range: &Range,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    let file_index = match p.found_file_index(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let content = match p.file_text(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let forms = match parse_sexprs(content.as_str(), fpath.to_str().unwrap()) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    let end = position_to_offset(content.as_str(), &range.end);
+    let candidates = enclosing(&forms, position_to_offset(content.as_str(), &range.start));
+    let form = match candidates.first() {
+        Some(x) if x.head() == Some("rule") => *x,
+        _ => return,
+    };
+    let mut exprs = Vec::new();
+    // terms and `let`s inside `if-let`s.
+    let mut iflet_exprs = Vec::new();
+    if let Some(provider) = p.found_file_defs(fpath) {
+        provider.with_rule(|r| {
+            if r.pos.file == file_index && form.contains_pos(&r.pos) {
+                for i in r.iflets.iter() {
+                    collect_exprs(&i.expr, &mut iflet_exprs);
+                    collect_exprs(&i.expr, &mut exprs);
+                }
+                collect_exprs(&r.expr, &mut exprs);
+            }
+        });
+    }
+    // innermost first.
+    let (e, sexpr) = match candidates.iter().rev().find_map(|c| {
+        if c.atom().is_some() || !c.contains(end) {
+            return None;
+        }
+        exprs
+            .iter()
+            .find(|x| {
+                let pos = expr_pos(x);
+                (pos.line, pos.col) == (c.start.line, c.start.col)
+            })
+            .map(|x| (x.clone(), *c))
+    }) {
+        Some(x) => x,
+        None => return,
+    };
+    let mut vars = Vec::new();
+    free_vars(&e, &mut Vec::new(), &mut vars);
+    let mut handler = VarTypes { vars: Vec::new() };
+    p.run_visitor_for_file(fpath, &mut handler);
+    let var_ty = |var: &Ident| {
+        handler
+            .vars
+            .iter()
+            .filter(|(x, _)| {
+                x.0 == var.0
+                    && x.1.file == var.1.file
+                    && form.contains_pos(&x.1)
+                    && (x.1.line, x.1.col) < (var.1.line, var.1.col)
+            })
+            .max_by_key(|(x, _)| (x.1.line, x.1.col))
+            .map(|(_, ty)| ty.clone())
+            .filter(|x| !x.is_empty())
+            .unwrap_or(PLACEHOLDER.to_string())
+    };
+    let types = expected_types(p, fpath);
+    let pos = expr_pos(&e);
+    let ret_ty = types
+        .iter()
+        .find(|(x, _)| x == &pos)
+        .map(|(_, ty)| ty.clone())
+        .or_else(|| match &e {
+            Expr::Term { sym, .. } => term_types(p, sym).map(|(x, _)| x),
+            _ => None,
+        })
+        .unwrap_or(PLACEHOLDER.to_string());
+    // terms in a `if-let` must be pure.
+    let modifiers = if iflet_exprs.iter().any(|x| expr_pos(x) == pos) {
+        decl_modifiers(p, &e)
+    } else {
+        String::new()
+    };
+    let name = new_name(p, NEW_ITEM_NAME);
+    let call = format!(
+        "({}{})",
+        name,
+        vars.iter().map(|x| format!(" {}", x.0)).collect::<String>()
+    );
+    let definition = format!(
+        "(decl {}{} ({}) {})\n(rule {} {})\n",
+        modifiers,
+        name,
+        vars.iter().map(var_ty).collect::<Vec<_>>().join(" "),
+        ret_ty,
+        call,
+        sexpr.text(content.as_str())
+    );
+    let insert = Position::new(form.start_line(), 0);
+    let mut changes = HashMap::new();
+    changes.insert(
+        Url::from_file_path(fpath).unwrap(),
+        vec![
+            TextEdit {
+                range: Range {
+                    start: insert,
+                    end: insert,
+                },
+                new_text: definition,
+            },
+            TextEdit {
+                range: sexpr.range(),
+                new_text: call,
+            },
+        ],
+    );
+    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Extract into constructor `{}`", name),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }));
+}
+
+/// `pure` and `partial` of the term `e` applies.
+fn decl_modifiers(p: &Project, e: &Expr) -> String {
+    match e {
+        Expr::Term { sym, .. } => match p.context.query_item_clone(&sym.0) {
+            Item::Decl { decl, .. } => format!(
+                "{}{}",
+                if decl.pure { "pure " } else { "" },
+                if decl.partial { "partial " } else { "" }
+            ),
+            _ => String::new(),
+        },
+        Expr::Let { body, .. } => decl_modifiers(p, body.as_ref()),
+        Expr::Var { .. } | Expr::ConstInt { .. } | Expr::ConstPrim { .. } => String::new(),
+    }
+}
+
+/// Terms and `let`s in `e`.
+fn collect_exprs(e: &Expr, exprs: &mut Vec<Expr>) {
+    match e {
+        Expr::Term { args, .. } => {
+            exprs.push(e.clone());
+            args.iter().for_each(|x| collect_exprs(x, exprs));
+        }
+        Expr::Let { defs, body, .. } => {
+            exprs.push(e.clone());
+            defs.iter()
+                .for_each(|x| collect_exprs(x.val.as_ref(), exprs));
+            collect_exprs(body.as_ref(), exprs);
+        }
+        Expr::Var { .. } | Expr::ConstInt { .. } | Expr::ConstPrim { .. } => {}
+    }
+}
+
+/// Variables used in `e` but not bound by a `let` of `e`,in order of first appearance.
+fn free_vars(e: &Expr, bound: &mut Vec<String>, vars: &mut Vec<Ident>) {
+    match e {
+        Expr::Var { name, .. } => {
+            if !bound.contains(&name.0) && vars.iter().all(|x| x.0 != name.0) {
+                vars.push(name.clone());
+            }
+        }
+        Expr::Term { args, .. } => args.iter().for_each(|x| free_vars(x, bound, vars)),
+        Expr::Let { defs, body, .. } => {
+            let len = bound.len();
+            for d in defs.iter() {
+                free_vars(d.val.as_ref(), bound, vars);
+                bound.push(d.var.0.clone());
+            }
+            free_vars(body.as_ref(), bound, vars);
+            bound.truncate(len);
+        }
+        Expr::ConstInt { .. } | Expr::ConstPrim { .. } => {}
+    }
+}
+
+/// Variables defined in a file with their types.
+struct VarTypes {
+    vars: Vec<(Ident, String)>,
+}
+
+impl ItemOrAccessHandler for VarTypes {
+    fn handle_item_or_access(&mut self, _p: &Project, item: &ItemOrAccess) {
+        if let ItemOrAccess::Item(Item::Var { name, ty, .. }) = item {
+            self.vars.push((name.clone(), ty.0.clone()));
+        }
+    }
+    fn visit_body(&self) -> bool {
+        true
+    }
+    fn finished(&self) -> bool {
+        false
+    }
+}
+
+impl std::fmt::Display for VarTypes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "collect types of variables")
+    }
+}
@@ -0,0 +1,284 @@
+//! Extract a sub-pattern of a rule into a extractor macro,
+//! variables of the sub-pattern become arguments of the macro.
+//! Occurrences are identical if they are the same after renaming variables.
+
+use super::converter::expected_types;
+use super::item::*;
+use super::project::*;
+use super::utils::pattern_pos;
+use crate::sexpr::{enclosing, parse_sexprs, position_to_offset, SExpr};
+use cranelift_isle::ast::{Ident, Pattern, Rule};
+use cranelift_isle::lexer::Pos;
+use lsp_types::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Extract the pattern selected by `range` into a extractor,
+/// and another action to replace all occurrences in the project.
+pub(crate) fn extract_extractor_actions(
+    p: &Project,
+    fpath: &Path,
+    range: &Range,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    let file_index = match p.found_file_index(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let content = match p.file_text(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let forms = match parse_sexprs(content.as_str(), fpath.to_str().unwrap()) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    let start = position_to_offset(content.as_str(), &range.start);
+    let end = position_to_offset(content.as_str(), &range.end);
+    let candidates = enclosing(&forms, start);
+    let form = match candidates.first() {
+        Some(x) if x.head() == Some("rule") => *x,
+        _ => return,
+    };
+    // innermost first.
+    let mut selected = None;
+    for c in candidates.iter().rev() {
+        if c.atom().is_some() || !c.contains(end) {
+            continue;
+        }
+        let mut found = None;
+        with_rule_patterns(p, |r, pat| {
+            if found.is_none() && r.pos.file == file_index && form.contains_pos(&r.pos) {
+                found = find_pattern(pat, &c.start).cloned();
+            }
+        });
+        if found.is_some() {
+            selected = found.map(|x| (x, *c));
+            break;
+        }
+    }
+    let (pat, sexpr) = match selected {
+        Some(x) => x,
+        None => return,
+    };
+    let mut vars = Vec::new();
+    let target = shape(&pat, &mut vars);
+    let types = expected_types(p, fpath);
+    let ty = |pos: &Pos| {
+        types
+            .iter()
+            .find(|(x, _)| x == pos)
+            .map(|(_, ty)| ty.as_str())
+            .unwrap_or(PLACEHOLDER)
+    };
+    let name = new_name(p, NEW_ITEM_NAME);
+    let definition = format!(
+        "(decl {} ({}) {})\n(extractor ({}{}) {})\n",
+        name,
+        vars.iter()
+            .map(|(_, pos)| ty(pos))
+            .collect::<Vec<_>>()
+            .join(" "),
+        ty(&pattern_pos(&pat)),
+        name,
+        vars.iter()
+            .map(|(x, _)| format!(" {}", x))
+            .collect::<String>(),
+        sexpr.text(content.as_str())
+    );
+    let insert = Position::new(form.start_line(), 0);
+    let insert = TextEdit {
+        range: Range {
+            start: insert,
+            end: insert,
+        },
+        new_text: definition,
+    };
+    let replace = |vars: &[(String, Pos)], range: Range| TextEdit {
+        range,
+        new_text: format!(
+            "({}{})",
+            name,
+            vars.iter()
+                .map(|(x, _)| format!(" {}", x))
+                .collect::<String>()
+        ),
+    };
+    let uri = Url::from_file_path(fpath).unwrap();
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![insert.clone(), replace(&vars, sexpr.range())],
+    );
+    let title = format!("Extract into extractor `{}`", name);
+    actions.push(extract_action(title, changes));
+
+    // all occurrences.
+    let mut occurrences = Vec::new();
+    with_rule_patterns(p, |_, pat| {
+        collect_occurrences(pat, &target, &mut occurrences)
+    });
+    if occurrences.len() <= 1 {
+        return;
+    }
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    changes.insert(uri.clone(), vec![insert]);
+    let mut files = HashMap::new();
+    for (pos, vars) in occurrences.iter() {
+        let file = files.entry(pos.file).or_insert_with(|| {
+            p.file_index_path(pos.file).and_then(|x| {
+                let content = p.file_text(&x)?;
+                let forms = parse_sexprs(content.as_str(), x.to_str().unwrap()).ok()?;
+                Some((Url::from_file_path(&x).ok()?, forms))
+            })
+        });
+        let (uri, forms) = match file {
+            Some(x) => x,
+            None => continue,
+        };
+        if let Some(x) = find_list(forms, pos) {
+            changes
+                .entry(uri.clone())
+                .or_default()
+                .push(replace(vars, x.range()));
+        }
+    }
+    let title = format!(
+        "Extract into extractor `{}` and replace {} occurrences",
+        name,
+        occurrences.len()
+    );
+    actions.push(extract_action(title, changes));
+}
+
+fn extract_action(title: String, changes: HashMap<Url, Vec<TextEdit>>) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// `base` or `base` with a number appended if it is taken.
+pub(crate) fn new_name(p: &Project, base: &str) -> String {
+    let mut name = base.to_string();
+    let mut index = 1;
+    while !matches!(p.context.query_item_clone(&name), Item::Dummy) {
+        name = format!("{}{}", base, index);
+        index += 1;
+    }
+    name
+}
+
+/// Call `call` with the left-hand side and `if-let` patterns of all rules.
+fn with_rule_patterns(p: &Project, mut call: impl FnMut(&Rule, &Pattern)) {
+    ProjectAstProvider::new(p).with_rule(|r| {
+        call(r, &r.pattern);
+        for i in r.iflets.iter() {
+            call(r, &i.pattern);
+        }
+    });
+}
+
+/// A term or `and` pattern at `pos`,the root term of a rule is not.
+fn find_pattern<'a>(pat: &'a Pattern, pos: &Pos) -> Option<&'a Pattern> {
+    fn find<'a>(pat: &'a Pattern, pos: &Pos) -> Option<&'a Pattern> {
+        match pat {
+            Pattern::Term { pos: x, .. } | Pattern::And { pos: x, .. }
+                if (x.line, x.col) == (pos.line, pos.col) =>
+            {
+                Some(pat)
+            }
+            _ => children(pat).into_iter().find_map(|x| find(x, pos)),
+        }
+    }
+    children(pat).into_iter().find_map(|x| find(x, pos))
+}
+
+fn children(pat: &Pattern) -> Vec<&Pattern> {
+    match pat {
+        Pattern::Term { args, .. } => args.iter().collect(),
+        Pattern::And { subpats, .. } => subpats.iter().collect(),
+        Pattern::BindPattern { subpat, .. } => vec![subpat.as_ref()],
+        Pattern::Var { .. }
+        | Pattern::ConstInt { .. }
+        | Pattern::ConstPrim { .. }
+        | Pattern::Wildcard { .. }
+        | Pattern::MacroArg { .. } => vec![],
+    }
+}
+
+/// Patterns have the same shape as `target`,with their variables.
+fn collect_occurrences(pat: &Pattern, target: &str, results: &mut Vec<(Pos, Vec<(String, Pos)>)>) {
+    for c in children(pat).into_iter() {
+        let mut vars = Vec::new();
+        if shape(c, &mut vars) == target {
+            results.push((pattern_pos(c), vars));
+        } else {
+            collect_occurrences(c, target, results);
+        }
+    }
+}
+
+/// `pat` in text with variables replaced by their index in `vars`,
+/// variables are pushed to `vars` at first appearance.
+fn shape(pat: &Pattern, vars: &mut Vec<(String, Pos)>) -> String {
+    let var = |name: &Ident, vars: &mut Vec<(String, Pos)>| {
+        let index = match vars.iter().position(|(x, _)| x == &name.0) {
+            Some(x) => x,
+            None => {
+                vars.push((name.0.clone(), name.1));
+                vars.len() - 1
+            }
+        };
+        format!("${}", index)
+    };
+    match pat {
+        Pattern::Var { var: name, .. } => var(name, vars),
+        Pattern::BindPattern {
+            var: name, subpat, ..
+        } => {
+            let name = var(name, vars);
+            format!("{} @ {}", name, shape(subpat.as_ref(), vars))
+        }
+        Pattern::ConstInt { val, .. } => val.to_string(),
+        Pattern::ConstPrim { val, .. } => val.0.clone(),
+        Pattern::Wildcard { .. } => "_".to_string(),
+        Pattern::MacroArg { index, .. } => format!("#{}", index),
+        Pattern::Term { sym, args, .. } => {
+            let mut ret = format!("({}", sym.0);
+            for a in args.iter() {
+                ret.push(' ');
+                ret.push_str(shape(a, vars).as_str());
+            }
+            ret.push(')');
+            ret
+        }
+        Pattern::And { subpats, .. } => {
+            let mut ret = "(and".to_string();
+            for a in subpats.iter() {
+                ret.push(' ');
+                ret.push_str(shape(a, vars).as_str());
+            }
+            ret.push(')');
+            ret
+        }
+    }
+}
+
+/// The list starts at `pos`.
+fn find_list<'a>(forms: &'a [SExpr], pos: &Pos) -> Option<&'a SExpr> {
+    forms.iter().find_map(|x| {
+        if x.atom().is_some() || !x.contains_pos(pos) {
+            None
+        } else if (x.start.line, x.start.col) == (pos.line, pos.col) {
+            Some(x)
+        } else {
+            find_list(x.children(), pos)
+        }
+    })
+}
@@ -0,0 +1,160 @@
+use super::context::*;
+use crate::comment::CommentExtrator;
+use crate::sexpr::{parse_sexprs, SExpr};
+use lsp_server::*;
+use lsp_types::*;
+
+/// Handles folding range request of the language server.
+pub fn on_folding_range_request(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<FoldingRangeParams>(request.params.clone())
+        .expect("could not deserialize folding range request");
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let content = context.project.file_text(&fpath).unwrap_or_default();
+    let ranges = folding_ranges(content.as_str(), fpath.to_str().unwrap());
+    let r = Response::new_ok(
+        request.id.clone(),
+        serde_json::to_value(Some(ranges)).unwrap(),
+    );
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
+
+/// Forms can be folded when nested,all top level forms are foldable.
+const FOLDABLE_FORMS: [&str; 2] = ["enum", "let"];
+
+fn folding_ranges(content: &str, filename: &str) -> Vec<FoldingRange> {
+    let mut ret = Vec::new();
+    if let Ok(forms) = parse_sexprs(content, filename) {
+        for f in forms.iter() {
+            collect_form(f, true, &mut ret);
+        }
+    }
+    collect_comments(content, &mut ret);
+    ret
+}
+
+fn collect_form(e: &SExpr, top_level: bool, ret: &mut Vec<FoldingRange>) {
+    if e.start_line() < e.end_line()
+        && (top_level
+            || e.head()
+                .map(|x| FOLDABLE_FORMS.contains(&x))
+                .unwrap_or(false))
+    {
+        ret.push(FoldingRange {
+            start_line: e.start_line(),
+            end_line: e.end_line(),
+            ..Default::default()
+        });
+    }
+    for c in e.children() {
+        collect_form(c, false, ret);
+    }
+}
+
+/// Banner like `;;;; Rules ;;;;;;;;` used in cranelift to split a file into sections.
+fn is_banner(line: &str) -> bool {
+    let line = line.trim();
+    line.starts_with(";;;;") && line.ends_with(';') && !line.trim_matches(';').trim().is_empty()
+}
+
+fn collect_comments(content: &str, ret: &mut Vec<FoldingRange>) {
+    let lines: Vec<_> = content.lines().collect();
+    let e = CommentExtrator::new(content);
+    // consecutive comment lines.
+    let mut run: Option<(u32, u32)> = None;
+    let flush = |run: &mut Option<(u32, u32)>, ret: &mut Vec<FoldingRange>| {
+        if let Some((start, end)) = run.take() {
+            if start < end {
+                ret.push(FoldingRange {
+                    start_line: start,
+                    end_line: end,
+                    kind: Some(FoldingRangeKind::Comment),
+                    ..Default::default()
+                });
+            }
+        }
+    };
+    let mut banners = Vec::new();
+    for c in e.comments().iter() {
+        let line = lines.get(c.line as usize).copied().unwrap_or("");
+        if is_banner(line) {
+            flush(&mut run, ret);
+            banners.push(c.line);
+            continue;
+        }
+        // comment after some code.
+        if !line.trim_start().starts_with(";;") {
+            continue;
+        }
+        run = match run {
+            Some((start, end)) if end + 1 == c.line => Some((start, c.line)),
+            _ => {
+                flush(&mut run, ret);
+                Some((c.line, c.line))
+            }
+        };
+    }
+    flush(&mut run, ret);
+    // a section ends before next banner.
+    for (index, start) in banners.iter().enumerate() {
+        let mut end = banners
+            .get(index + 1)
+            .map(|x| *x as usize)
+            .unwrap_or(lines.len());
+        while end > 0
+            && lines
+                .get(end - 1)
+                .map(|x| x.trim().is_empty())
+                .unwrap_or(true)
+        {
+            end -= 1;
+        }
+        let end = end.saturating_sub(1) as u32;
+        if *start < end {
+            ret.push(FoldingRange {
+                start_line: *start,
+                end_line: end,
+                kind: Some(FoldingRangeKind::Region),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_folding_ranges() {
+    let content = r#";;;; Types ;;;;;;;;
+
+;; first line
+;; second line
+(type Inst
+  (enum
+    (A)
+    (B)))
+
+;;;; Rules ;;;;;;;;
+(decl a (u32) u32)
+(rule (a x)
+  (let ((y u32 x))
+    y))
+"#;
+    let ranges = folding_ranges(content, "");
+    let has = |start: u32, end: u32, kind: Option<FoldingRangeKind>| {
+        ranges
+            .iter()
+            .any(|x| x.start_line == start && x.end_line == end && x.kind == kind)
+    };
+    assert!(has(0, 7, Some(FoldingRangeKind::Region)));
+    assert!(has(9, 13, Some(FoldingRangeKind::Region)));
+    assert!(has(2, 3, Some(FoldingRangeKind::Comment)));
+    assert!(has(4, 7, None));
+    assert!(has(5, 7, None));
+    assert!(has(11, 13, None));
+    assert!(has(12, 13, None));
+    // single line form.
+    assert!(!has(10, 10, None));
+}
@@ -2,6 +2,7 @@ use super::context::Context;
 use super::goto_definition;
 use super::item::*;
 use super::project::Project;
+use cranelift_isle::ast::TypeValue;
 use lsp_server::*;
 use lsp_types::*;
 
@@ -37,7 +38,7 @@ pub fn on_hover_request(context: &Context, request: &Request) {
         .unwrap();
 }
 
-fn hover_on_item_or_access(ia: &ItemOrAccess, p: &Project) -> String {
+pub(crate) fn hover_on_item_or_access(ia: &ItemOrAccess, p: &Project) -> String {
     let item_hover = |item: &Item| -> String {
         let pos = item.def_loc();
         let fpath = p.file_index_path(pos.0.file);
@@ -59,6 +60,31 @@ fn hover_on_item_or_access(ia: &ItemOrAccess, p: &Project) -> String {
                 }
                 s
             }
+            Item::Const { name, ty } => {
+                format!(
+                    "(extern const ${} {})\n\nrust type:{}",
+                    name.0,
+                    ty.0,
+                    rust_type(&ty.0, p)
+                )
+            }
+            Item::ConstInt { lit, val, ty } => {
+                let ty = if !ty.0.is_empty() {
+                    format!("{}\n\nrust type:{}", ty.0, rust_type(&ty.0, p))
+                } else {
+                    "unknown".to_string()
+                };
+                let (sign, abs) = if *val < 0 {
+                    ("-", val.unsigned_abs())
+                } else {
+                    ("", *val as u128)
+                };
+                format!(
+                    "{}:{}\n\ndec:{}{}\n\nhex:{}{:#x}\n\nbin:{}{:#b}",
+                    lit.0, ty, sign, abs, sign, abs, sign, abs
+                )
+            }
+            Item::ExternFunc { name } => format!("rust function:{}", name.0),
             _ => format!("{}", item),
         };
         format!("{}\n{}", comment, item_str)
@@ -68,3 +94,14 @@ fn hover_on_item_or_access(ia: &ItemOrAccess, p: &Project) -> String {
         ItemOrAccess::Access(acc) => item_hover(acc.def_item()),
     }
 }
+
+/// The Rust type a `ISLE` type maps to.
+fn rust_type(ty: &str, p: &Project) -> String {
+    match p.context.query_item_clone(ty) {
+        Item::Type { ty } => match &ty.ty {
+            TypeValue::Primitive(x, _) => x.0.clone(),
+            TypeValue::Enum(_, _) => ty.name.0.clone(),
+        },
+        _ => "unknown".to_string(),
+    }
+}
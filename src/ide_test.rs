@@ -4,7 +4,7 @@ use super::*;
 use crate::utils::*;
 use log::*;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 struct SimpleLogger;
@@ -69,3 +69,924 @@ fn completion() {
         eprintln!("xxxx:{:?}->{:?}", x.label, x.kind)
     }
 }
+
+#[test]
+fn hover_const_int() {
+    let file = path_to_abs("./tests/bound_var.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let mut handler =
+        goto_definition::Handler::new(url::Url::from_file_path(file.clone()).unwrap(), 6, 5);
+    p.run_visitor_for_file(&file, &mut handler);
+    match handler.result_item_or_access.unwrap() {
+        item::ItemOrAccess::Item(item::Item::ConstInt { lit, val, ty }) => {
+            assert_eq!(lit.0, "100");
+            assert_eq!(val, 100);
+            assert_eq!(ty.0, "u32");
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn hover_const() {
+    let file = path_to_abs("./tests/hover_const.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let u = url::Url::from_file_path(file.clone()).unwrap();
+    let hover = |line, col| {
+        let mut handler = goto_definition::Handler::new(u.clone(), line, col);
+        p.run_visitor_for_file(&file, &mut handler);
+        handler.result_item_or_access.unwrap()
+    };
+    // literals nested in an extractor template and an `and` pattern.
+    for (line, col, lit) in [(6, 22, "7"), (8, 16, "3")] {
+        match hover(line, col) {
+            item::ItemOrAccess::Item(item::Item::ConstInt { lit: l, ty, .. }) => {
+                assert_eq!(l.0, lit);
+                assert_eq!(ty.0, "u8");
+            }
+            _ => unreachable!(),
+        }
+    }
+    let ia = hover(8, 21);
+    match &ia {
+        item::ItemOrAccess::Access(a) => match &a.def {
+            item::Item::Const { name, ty } => {
+                assert_eq!(name.0, "ZERO");
+                assert_eq!(ty.0, "u32");
+            }
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+    assert!(hover::hover_on_item_or_access(&ia, &p).contains("(extern const $ZERO u32)"));
+}
+
+#[test]
+fn document_highlight() {
+    let file = path_to_abs("./tests/document_highlight.isle");
+    let other = path_to_abs("./tests/document_highlight_other.isle");
+    let p = Project::new(vec![file.clone(), other]).unwrap();
+    let u = url::Url::from_file_path(file.clone()).unwrap();
+    let highlights = |line, col| {
+        let mut v: Vec<_> = document_highlight::document_highlights(&p, &u, line, col)
+            .into_iter()
+            .map(|x| (x.range.start.line, x.range.start.character, x.kind.unwrap()))
+            .collect();
+        v.sort_by_key(|x| (x.0, x.1));
+        v
+    };
+    // `f` is also called in the other file.
+    let text = lsp_types::DocumentHighlightKind::TEXT;
+    assert_eq!(
+        highlights(1, 6),
+        vec![(1, 6, text), (2, 7, text), (3, 9, text), (3, 15, text)]
+    );
+    // `x` in the first rule only.
+    assert_eq!(
+        highlights(2, 9),
+        vec![
+            (2, 9, lsp_types::DocumentHighlightKind::WRITE),
+            (2, 12, lsp_types::DocumentHighlightKind::READ)
+        ]
+    );
+}
+
+#[test]
+fn rename_validation() {
+    let file = path_to_abs("./tests/bound_var.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let u = url::Url::from_file_path(file.clone()).unwrap();
+    // keyword
+    assert!(rename::rename_edits(&p, &u, 3, 6, "rule").is_err());
+    // clash with type `u32`
+    assert!(rename::rename_edits(&p, &u, 3, 6, "u32").is_err());
+    // nothing to rename on `decl`
+    assert!(rename::rename_edits(&p, &u, 3, 2, "B").is_err());
+    let edits = rename::rename_edits(&p, &u, 3, 6, "B").unwrap();
+    assert_eq!(edits.get(&u).unwrap().len(), 2);
+}
+
+#[test]
+fn rename_var_validation() {
+    let file = path_to_abs("./tests/rename_var.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let u = url::Url::from_file_path(file.clone()).unwrap();
+    // clash with `y` in the same rule.
+    assert!(rename::rename_edits(&p, &u, 2, 9, "y").is_err());
+    // clash with the top level decl `f`.
+    assert!(rename::rename_edits(&p, &u, 2, 9, "f").is_err());
+    // wildcard and keyword.
+    assert!(rename::rename_edits(&p, &u, 2, 9, "_").is_err());
+    assert!(rename::rename_edits(&p, &u, 2, 9, "rule").is_err());
+    let edits = rename::rename_edits(&p, &u, 2, 9, "z").unwrap();
+    assert_eq!(edits.get(&u).unwrap().len(), 2);
+}
+
+#[test]
+fn rename_enum() {
+    let file = path_to_abs("./tests/enum_rename.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let u = url::Url::from_file_path(file.clone()).unwrap();
+    let starts = |edits: &HashMap<url::Url, Vec<lsp_types::TextEdit>>, len: u32| {
+        let mut ret: Vec<_> = edits
+            .get(&u)
+            .unwrap()
+            .iter()
+            .map(|e| {
+                assert_eq!(e.range.start.line, e.range.end.line);
+                assert_eq!(e.range.end.character - e.range.start.character, len);
+                (e.range.start.line, e.range.start.character)
+            })
+            .collect();
+        ret.sort();
+        ret
+    };
+    // type half of `Shape.Square`.
+    let edits = rename::rename_edits(&p, &u, 11, 14, "Form").unwrap();
+    assert_eq!(
+        starts(&edits, 5),
+        vec![(2, 6), (7, 12), (11, 13), (13, 13), (16, 19), (17, 18)]
+    );
+    // variant half of `Shape.Square`.
+    let edits = rename::rename_edits(&p, &u, 11, 20, "Quad").unwrap();
+    assert_eq!(starts(&edits, 6), vec![(4, 5), (11, 19), (17, 24)]);
+    // on the variant definition.
+    let edits = rename::rename_edits(&p, &u, 5, 6, "Rectangle").unwrap();
+    assert_eq!(starts(&edits, 4), vec![(5, 5), (13, 19)]);
+    assert!(rename::rename_edits(&p, &u, 11, 20, "Rect").is_err());
+    // field name.
+    let edits = rename::rename_edits(&p, &u, 5, 12, "w").unwrap();
+    assert_eq!(starts(&edits, 5), vec![(5, 11)]);
+    assert!(rename::rename_edits(&p, &u, 5, 12, "height").is_err());
+}
+
+#[test]
+fn rename_extern_func() {
+    let file = path_to_abs("./tests/enum_rename.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let u = url::Url::from_file_path(file.clone()).unwrap();
+    let dir = std::env::temp_dir().join("isle_analyzer_rename_extern_func");
+    let _ = std::fs::create_dir_all(&dir);
+    let rs = dir.join("lib.rs");
+    std::fs::write(
+        &rs,
+        "impl Context for IsleContext {\n    fn mul(&mut self, a: u32, b: u32) -> u32 {\n        a * b\n    }\n}\nfn f(c: &mut IsleContext) -> u32 {\n    c.mul(1, 2)\n}\n",
+    )
+    .unwrap();
+    let config = config::ServerConfig {
+        rust_dirs: vec![dir.clone()],
+        ..Default::default()
+    };
+    let edit = rename::rename_workspace_edit(&p, &config, &u, 9, 25, "multiply").unwrap();
+    let changes = match edit.document_changes.unwrap() {
+        lsp_types::DocumentChanges::Edits(x) => x,
+        _ => unreachable!(),
+    };
+    assert_eq!(changes.len(), 2);
+    assert_eq!(changes[0].text_document.uri, u);
+    assert_eq!(changes[0].edits.len(), 1);
+    match &changes[0].edits[0] {
+        lsp_types::OneOf::Left(e) => assert_eq!(e.range.start, lsp_types::Position::new(9, 24)),
+        _ => unreachable!(),
+    }
+    assert_eq!(changes[1].edits.len(), 2);
+    for e in changes[1].edits.iter() {
+        match e {
+            lsp_types::OneOf::Right(e) => assert_eq!(e.annotation_id, "rust"),
+            _ => unreachable!(),
+        }
+    }
+    let annotation = edit.change_annotations.unwrap();
+    assert_eq!(
+        annotation.get("rust").unwrap().needs_confirmation,
+        Some(true)
+    );
+    // clash with a Rust function.
+    assert!(rename::rename_workspace_edit(&p, &config, &u, 9, 25, "f").is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn implicit_conversions() {
+    let file = path_to_abs("./tests/convert.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let mut got: Vec<_> = converter::implicit_conversions(&p, &file)
+        .into_iter()
+        .map(|c| (c.pos.line, c.pos.col, c.term.0, c.inner, c.outer))
+        .collect();
+    got.sort();
+    let s = |x: &str| x.to_string();
+    // `put_in_reg` has no extractor,so `(value_of _)` of line 17 is not converted.
+    assert_eq!(
+        got,
+        vec![
+            (15, 23, s("put_in_reg"), s("Value"), s("Reg")),
+            (24, 23, s("imm_to_reg"), s("Imm"), s("Reg")),
+        ]
+    );
+}
+
+#[test]
+fn server_config() {
+    let c = config::ServerConfig::from_value(Some(&serde_json::json!({
+        "rustDirs": ["/a"],
+        "inlayHints": {"argumentTypes": true, "variableTypes": false},
+        "server": {"path": "isle-analyzer"},
+    })));
+    assert_eq!(c.rust_dirs, vec![PathBuf::from("/a")]);
+    assert!(c.inlay_hints.argument_types);
+    assert!(!c.inlay_hints.variable_types);
+    assert!(c.inlay_hints.implicit_conversions);
+    assert!(
+        config::ServerConfig::from_value(None)
+            .inlay_hints
+            .variable_types
+    );
+}
+
+#[test]
+fn ast_inlay_hints() {
+    let file = path_to_abs("./tests/convert.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let hints = inlay_hitnt::inlay_hints(
+        &p,
+        url::Url::from_file_path(&file).unwrap(),
+        lsp_types::Range {
+            start: lsp_types::Position::new(0, 0),
+            end: lsp_types::Position::new(100, 0),
+        },
+        &config::InlayHintsConfig {
+            variable_types: false,
+            implicit_conversions: false,
+            argument_types: true,
+            return_types: true,
+            rule_priorities: true,
+        },
+    );
+    let got: Vec<_> = hints
+        .iter()
+        .filter(|x| x.position.line == 14)
+        .map(|x| {
+            let label = match &x.label {
+                lsp_types::InlayHintLabel::String(s) => s.clone(),
+                lsp_types::InlayHintLabel::LabelParts(parts) => parts[0].value.clone(),
+            };
+            (x.position.character, label)
+        })
+        .collect();
+    let s = |x: u32, y: &str| (x, y.to_string());
+    for x in [
+        s(5, "0"),
+        s(13, "Value:"),
+        s(15, "Value:"),
+        s(17, "Reg"),
+        s(23, "Reg:"),
+        s(25, "Reg:"),
+        s(37, "Value:"),
+        s(40, "Reg"),
+    ] {
+        assert!(got.contains(&x), "{:?} not in {:?}", x, got);
+    }
+    // the priority is not a parameter.
+    assert!(hints
+        .iter()
+        .any(|x| (x.position.line, x.position.character) == (14, 5) && x.kind.is_none()));
+}
+
+#[test]
+fn sema_var_types() {
+    struct Vars(Vec<(String, usize, String)>, Vec<(String, usize)>);
+    impl ItemOrAccessHandler for Vars {
+        fn handle_item_or_access(&mut self, _p: &Project, item: &item::ItemOrAccess) {
+            match item {
+                item::ItemOrAccess::Item(item::Item::Var { name, ty, .. }) => {
+                    self.0.push((name.0.clone(), name.1.line, ty.0.clone()))
+                }
+                item::ItemOrAccess::Access(x) => self.1.push((x.access.0.clone(), x.access.1.line)),
+                _ => {}
+            }
+        }
+        fn visit_body(&self) -> bool {
+            true
+        }
+        fn finished(&self) -> bool {
+            false
+        }
+    }
+    impl std::fmt::Display for Vars {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "collect vars")
+        }
+    }
+    let file = path_to_abs("./tests/sema_info.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    assert!(p.sema().is_some());
+    let mut vars = Vars(Vec::new(), Vec::new());
+    p.run_visitor_for_file(&file, &mut vars);
+    let v = |name: &str, line: usize, ty: &str| (name.to_string(), line, ty.to_string());
+    // `z` is bound by the first `if-let`,unknown to the visitor.
+    assert_eq!(
+        vars.0,
+        vec![
+            v("x", 8, "u32"),
+            v("y", 8, "u32"),
+            v("z", 9, "Reg"),
+            v("w", 11, "Reg")
+        ]
+    );
+    // the second is a equality check.
+    assert!(vars.1.contains(&("z".to_string(), 10)));
+}
+
+#[test]
+fn lint_diagnostics() {
+    use config::ServerConfig;
+    use cranelift_isle::ast::Def;
+    use lint::*;
+    use lsp_types::DiagnosticSeverity;
+    /// Every `decl` is reported.
+    struct DeclLint;
+    impl Lint for DeclLint {
+        fn name(&self) -> &'static str {
+            "decl"
+        }
+        fn default_level(&self) -> LintLevel {
+            LintLevel::Warning
+        }
+        fn check(&self, p: &Project, results: &mut Vec<LintResult>) {
+            ProjectAstProvider::new(p).with_def(|d| {
+                if let Def::Decl(d) = d {
+                    results.push(LintResult {
+                        loc: p.mk_location(&d.term).unwrap(),
+                        message: format!("decl {}", d.term.0),
+                        tags: vec![],
+                    });
+                }
+            });
+        }
+    }
+    assert_eq!(
+        parse_allow(" isle-analyzer: allow(decl, other)"),
+        Some(vec!["decl".to_string(), "other".to_string()])
+    );
+    assert_eq!(parse_allow(" allow(decl)"), None);
+    let file = path_to_abs("./tests/lint.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let lints: Vec<Box<dyn Lint>> = vec![Box::new(DeclLint)];
+    let ds = lint_diagnostics(&p, &ServerConfig::default(), &lints);
+    let got: Vec<_> = ds[&file]
+        .iter()
+        .map(|d| (d.range.start.line, d.message.clone(), d.severity))
+        .collect();
+    assert_eq!(
+        got,
+        vec![(2, "decl A".to_string(), Some(DiagnosticSeverity::WARNING))]
+    );
+    let config = ServerConfig::from_value(Some(&serde_json::json!({
+        "lints": {"decl": "allow"}
+    })));
+    let ds = lint_diagnostics(&p, &config, &lints);
+    assert_eq!(ds[&file].len(), 0);
+}
+
+#[test]
+fn dead_code() {
+    use dead_code::DeadCodeKind;
+    let file = path_to_abs("./tests/dead_code.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let got: Vec<_> = dead_code::dead_code(&p)
+        .into_iter()
+        .map(|d| (d.kind, d.name, d.location.range.start.line))
+        .collect();
+    let s = |k: DeadCodeKind, x: &str, line: u32| (k, x.to_string(), line);
+    assert_eq!(
+        got,
+        vec![
+            s(DeadCodeKind::Type, "u8", 1),
+            s(DeadCodeKind::Variant, "C", 2),
+            s(DeadCodeKind::Const, "unused_const", 5),
+            s(DeadCodeKind::Decl, "unused_extern", 10),
+            s(DeadCodeKind::Decl, "unused_decl", 12),
+            // rules but no callers.
+            s(DeadCodeKind::Decl, "helper", 14),
+        ]
+    );
+}
+
+#[test]
+fn missing_impl() {
+    let file = path_to_abs("./tests/missing_impl.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let mut results = Vec::new();
+    use lint::Lint;
+    missing_impl::MissingImpl.check(&p, &mut results);
+    let got: Vec<_> = results
+        .iter()
+        .map(|r| (r.loc.range.start.line, r.message.as_str()))
+        .collect();
+    assert_eq!(got.len(), 4);
+    assert_eq!(got[0].0, 3);
+    assert!(got[0].1.contains("used as a extractor but"));
+    assert_eq!(got[1].0, 4);
+    assert!(got[1].1.contains("used as a constructor but"));
+    assert_eq!(got[2].0, 5);
+    assert!(got[2].1.contains("neither implementation"));
+    assert_eq!(got[3].0, 11);
+    assert!(got[3].1.contains("no rules"));
+}
+
+#[test]
+fn unused_vars() {
+    let file = path_to_abs("./tests/unused_var.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let got: Vec<_> = unused_var::unused_vars(ProjectAstProvider::new(&p))
+        .into_iter()
+        .map(|v| (v.var.0, v.var.1.line, v.var.1.col, v.subpat.is_some()))
+        .collect();
+    let s = |x: &str, line: usize, col: usize, bind: bool| (x.to_string(), line, col, bind);
+    assert_eq!(
+        got,
+        vec![
+            s("y", 4, 13, false),
+            s("a", 6, 11, true),
+            s("z", 7, 24, false),
+            s("x", 8, 11, false),
+        ]
+    );
+}
+
+pub(crate) fn action_params(file: &Path, line: u32, col: u32) -> lsp_types::CodeActionParams {
+    let pos = lsp_types::Position::new(line, col);
+    lsp_types::CodeActionParams {
+        text_document: lsp_types::TextDocumentIdentifier {
+            uri: lsp_types::Url::from_file_path(file).unwrap(),
+        },
+        range: lsp_types::Range {
+            start: pos,
+            end: pos,
+        },
+        context: Default::default(),
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    }
+}
+
+/// Title and edits of each action,edits are sorted by position.
+pub(crate) fn action_edits(
+    actions: Vec<lsp_types::CodeActionOrCommand>,
+) -> Vec<(String, Vec<(lsp_types::Range, String)>)> {
+    actions
+        .into_iter()
+        .map(|x| match x {
+            lsp_types::CodeActionOrCommand::CodeAction(x) => {
+                let edit = x.edit.unwrap();
+                let mut edits: Vec<_> = match edit.document_changes {
+                    Some(lsp_types::DocumentChanges::Edits(edits)) => edits
+                        .into_iter()
+                        .flat_map(|x| x.edits)
+                        .map(|x| match x {
+                            lsp_types::OneOf::Left(x) => x,
+                            lsp_types::OneOf::Right(x) => x.text_edit,
+                        })
+                        .collect(),
+                    _ => edit.changes.unwrap().into_values().flatten().collect(),
+                };
+                edits.sort_by_key(|x| x.range.start);
+                let edits = edits.into_iter().map(|x| (x.range, x.new_text)).collect();
+                (x.title, edits)
+            }
+            lsp_types::CodeActionOrCommand::Command(_) => unreachable!(),
+        })
+        .collect()
+}
+
+#[test]
+fn unused_var_fixes() {
+    let file = path_to_abs("./tests/unused_var.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let fixes = |line: u32, col: u32| {
+        let mut actions = Vec::new();
+        code_action::unused_var_fixes(&p, &file, &action_params(&file, line, col), &mut actions);
+        action_edits(actions)
+    };
+    let r = |line: u32, start: u32, end: u32| Range {
+        start: Position::new(line, start),
+        end: Position::new(line, end),
+    };
+    assert_eq!(
+        fixes(3, 13),
+        vec![(
+            "Replace `y` with `_`".to_string(),
+            vec![(r(3, 13, 14), "_".to_string())]
+        )]
+    );
+    assert_eq!(
+        fixes(5, 12),
+        vec![(
+            "Remove unused bind `a @`".to_string(),
+            vec![(r(5, 11, 15), String::new())]
+        )]
+    );
+    assert_eq!(fixes(4, 11).len(), 0);
+}
+
+#[test]
+fn create_decl_fixes() {
+    let file = path_to_abs("./tests/create_decl.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let fixes = |line: u32, col: u32| {
+        let mut actions = Vec::new();
+        code_action::create_decl_fixes(&p, &file, &action_params(&file, line, col), &mut actions);
+        action_edits(actions)
+    };
+    let pos = |line: u32, col: u32| {
+        let x = Position::new(line, col);
+        Range { start: x, end: x }
+    };
+    assert_eq!(
+        fixes(4, 18),
+        vec![(
+            "Create `(decl new_ctor (u32 Reg) Reg)`".to_string(),
+            vec![(pos(2, 22), "\n(decl new_ctor (u32 Reg) Reg)".to_string())]
+        )]
+    );
+    assert_eq!(
+        fixes(5, 17),
+        vec![(
+            "Create `(decl new_ext (TODO) u32)`".to_string(),
+            vec![(pos(2, 22), "\n(decl new_ext (TODO) u32)".to_string())]
+        )]
+    );
+    assert_eq!(
+        fixes(7, 7),
+        vec![(
+            "Create `(decl new_root (TODO) TODO)`".to_string(),
+            vec![(pos(7, 0), "(decl new_root (TODO) TODO)\n".to_string())]
+        )]
+    );
+}
+
+#[test]
+fn prio_fixes() {
+    let file = path_to_abs("./tests/prio_fix.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let fixes = |line: u32| {
+        let range = lsp_types::Range {
+            start: Position::new(line, 1),
+            end: Position::new(line, 1),
+        };
+        let mut actions = Vec::new();
+        prio_fix::prio_fixes(&p, &file, &range, &mut actions);
+        action_edits(actions)
+            .into_iter()
+            .map(|(_, edits)| (edits[0].0.start, edits[0].1.clone()))
+            .collect::<Vec<_>>()
+    };
+    // overlap,only raising the specific rule don't shadow the other one.
+    let bump_f = vec![(Position::new(3, 5), " 1".to_string())];
+    assert_eq!(fixes(2), bump_f);
+    assert_eq!(fixes(3), bump_f);
+    // shadowed by a rule with priority 1.
+    assert_eq!(fixes(6), vec![(Position::new(6, 5), " 2".to_string())]);
+    assert_eq!(fixes(0).len(), 0);
+    // raising `(h 1 y)` overlaps or shadows `(h 1 2)`.
+    assert_eq!(fixes(9), vec![(Position::new(8, 5), " 1".to_string())]);
+    let d = |code: &str| lsp_types::Diagnostic {
+        code: Some(lsp_types::NumberOrString::String(code.to_string())),
+        ..Default::default()
+    };
+    assert!(prio_fix::is_conflict(&d(prio_fix::SHADOWED_CODE)));
+    assert!(!prio_fix::is_conflict(&d("unused-variable")));
+}
+
+#[test]
+fn explain_overlap() {
+    let file = path_to_abs("./tests/overlap.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let got: Vec<_> = overlap::explain_overlap(&p, &file, lsp_types::Position::new(5, 3))
+        .unwrap()
+        .into_iter()
+        .map(|x| (x.rule.range.start.line, x.witness))
+        .collect();
+    assert_eq!(
+        got,
+        vec![(6, "(lower (E.A 1) (and (ext _) 2))".to_string())]
+    );
+    assert!(overlap::explain_overlap(&p, &file, lsp_types::Position::new(7, 3)).is_none());
+    // the same value written differently.
+    let got: Vec<_> = overlap::explain_overlap(&p, &file, lsp_types::Position::new(9, 3))
+        .unwrap()
+        .into_iter()
+        .map(|x| (x.rule.range.start.line, x.witness))
+        .collect();
+    assert_eq!(got, vec![(10, "(lower2 0x10)".to_string())]);
+    // `x` appears twice,`(lower3 1 _)` would be wrong.
+    let rules: Vec<_> = p
+        .defs
+        .defs
+        .iter()
+        .filter_map(|d| match d {
+            cranelift_isle::ast::Def::Rule(r) if r.pos.line > 12 => Some(r.pos),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(rules.len(), 2);
+    assert!(overlap::overlap_witness(&p, &rules[0], &rules[1]).is_none());
+    assert!(overlap::explain_overlap(&p, &file, lsp_types::Position::new(12, 3)).is_none());
+    // deleted defs leave placeholders behind.
+    let mut p = p;
+    let content = std::fs::read_to_string(&file)
+        .unwrap()
+        .replace("(rule (lower (E.B) _) 1)", "")
+        .replace("(rule (lower2 16) 1)", "");
+    p.update_defs(&file, content.as_str()).unwrap();
+    assert!(overlap::explain_overlap(&p, &file, lsp_types::Position::new(5, 3)).is_some());
+}
+
+#[test]
+fn change_signature() {
+    let file = path_to_abs("./tests/change_signature.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let decl = change_signature::decl_at(&p, &file, 2, 6).unwrap();
+    assert_eq!(decl.term.0, "f");
+    let edit = change_signature::change_signature(
+        &p,
+        &decl,
+        &[
+            change_signature::NewArg::Old(1),
+            change_signature::NewArg::New("Reg".to_string()),
+            change_signature::NewArg::Old(0),
+        ],
+    )
+    .unwrap();
+    let mut edits = edit.changes.unwrap().into_values().next().unwrap();
+    edits.sort_by_key(|x| x.range.start);
+    let got: Vec<_> = edits
+        .iter()
+        .map(|x| (x.range.start.line, x.new_text.as_str()))
+        .collect();
+    // the `f` bound by `let` at line 15 is not a application.
+    assert_eq!(
+        got,
+        vec![
+            (2, "(decl f (u8 Reg u32) u32)"),
+            (3, "(rule (f x _ (f _ _ y)) (f x TODO (g y)))"),
+            // `arg1` is taken.
+            (4, "(extractor (f arg1 arg11 a) (h a arg1))"),
+            (
+                10,
+                "(rule (lower x)\n  ;; keep me\n  (if-let (f _ _ z) (g x))\n  (f 1 TODO z))"
+            ),
+        ]
+    );
+    assert!(
+        change_signature::change_signature(&p, &decl, &[change_signature::NewArg::Old(2)]).is_err()
+    );
+}
+
+#[test]
+fn extract_extractor() {
+    let file = path_to_abs("./tests/extract_extractor.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let mut actions = Vec::new();
+    extract_extractor::extract_extractor_actions(
+        &p,
+        &file,
+        &action_params(&file, 7, 14).range,
+        &mut actions,
+    );
+    let r = |line: u32, start: u32, end: u32| Range {
+        start: Position::new(line, start),
+        end: Position::new(line, end),
+    };
+    let insert = (
+        r(7, 0, 0),
+        "(decl extracted (Value u32) Value)\n(extractor (extracted x k) (iadd x (iconst k)))\n"
+            .to_string(),
+    );
+    let replace = |line: u32, col: u32, text: &str| (r(line, col, col + 19), text.to_string());
+    assert_eq!(
+        action_edits(actions),
+        vec![
+            (
+                "Extract into extractor `extracted`".to_string(),
+                vec![insert.clone(), replace(7, 13, "(extracted x k)")]
+            ),
+            (
+                "Extract into extractor `extracted` and replace 2 occurrences".to_string(),
+                vec![
+                    insert,
+                    replace(7, 13, "(extracted x k)"),
+                    replace(9, 15, "(extracted y c)")
+                ]
+            ),
+        ]
+    );
+}
+
+#[test]
+fn inline() {
+    let file = path_to_abs("./tests/inline.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let inline = |line: u32, col: u32| {
+        let mut actions = Vec::new();
+        inline::inline_actions(
+            &p,
+            &file,
+            &action_params(&file, line, col).range,
+            &mut actions,
+        );
+        action_edits(actions)
+    };
+    let e = |line: u32, col: u32, text: &str| (line, col, text.to_string());
+    let starts = |x: Vec<(String, Vec<(Range, String)>)>| {
+        x.into_iter()
+            .map(|(title, edits)| {
+                let edits: Vec<_> = edits
+                    .into_iter()
+                    .map(|(r, text)| (r.start.line, r.start.character, text))
+                    .collect();
+                (title, edits)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        starts(inline(10, 14)),
+        vec![
+            ("Inline `small`".to_string(), vec![e(10, 13, "(iconst k)")]),
+            (
+                "Inline `small` and remove its definition".to_string(),
+                vec![e(3, 0, ""), e(4, 0, ""), e(10, 13, "(iconst k)")]
+            ),
+        ]
+    );
+    assert_eq!(
+        starts(inline(10, 25)),
+        vec![(
+            "Inline `double`".to_string(),
+            vec![e(10, 24, "(let ((x u32 (add k 1))) (add x x))")]
+        )]
+    );
+    assert_eq!(
+        starts(inline(11, 28)),
+        vec![("Inline `double`".to_string(), vec![e(11, 27, "(add 2 2)")])]
+    );
+    // extern constructor.
+    assert_eq!(inline(8, 19).len(), 0);
+    // `x` of the caller is not captured by the parameter `x`.
+    assert_eq!(
+        starts(inline(19, 20))[0],
+        (
+            "Inline `f`".to_string(),
+            vec![e(19, 19, "(let ((x1 u32 (h a))) (g x1 (g x1 x)))")]
+        )
+    );
+    // `x` of `both` appears twice.
+    assert_eq!(inline(25, 16).len(), 0);
+    // `(same x x)` requires equal arguments.
+    assert_eq!(inline(25, 26).len(), 0);
+}
+
+#[test]
+fn move_decl() {
+    let file = path_to_abs("./tests/move_decl.isle");
+    let to = path_to_abs("./tests/move_decl_to.isle");
+    let p = Project::new(vec![file.clone(), to.clone()]).unwrap();
+    let decl = change_signature::decl_at(&p, &file, 4, 11).unwrap();
+    assert_eq!(decl.term.0, "inc");
+    let ops = |to: &PathBuf| match move_decl::move_decl(&p, &decl, to)
+        .unwrap()
+        .document_changes
+    {
+        Some(lsp_types::DocumentChanges::Operations(x)) => x,
+        _ => unreachable!(),
+    };
+    let edits = |op: &lsp_types::DocumentChangeOperation| match op {
+        lsp_types::DocumentChangeOperation::Edit(x) => x
+            .edits
+            .iter()
+            .map(|x| match x {
+                lsp_types::OneOf::Left(x) => {
+                    (x.range.start.line, x.range.end.line, x.new_text.clone())
+                }
+                lsp_types::OneOf::Right(_) => unreachable!(),
+            })
+            .collect::<Vec<_>>(),
+        lsp_types::DocumentChangeOperation::Op(_) => unreachable!(),
+    };
+    let text = ";; Add one.\n;; It is pure.\n(decl pure inc (u32) u32)\n;; inc of zero.\n(rule (inc 0) 1)\n(rule -1 (inc x) (dec x))\n(extern extractor inc inc)\n";
+    let removes = vec![
+        (2, 5, String::new()),
+        (7, 9, String::new()),
+        (9, 10, String::new()),
+        (10, 11, String::new()),
+    ];
+    let got = ops(&to);
+    assert_eq!(got.len(), 2);
+    assert_eq!(edits(&got[0]), vec![(1, 1, format!("\n{}", text))]);
+    assert_eq!(edits(&got[1]), removes);
+
+    let new_file = path_to_abs("./tests/move_decl_new.isle");
+    let got = ops(&new_file);
+    assert_eq!(got.len(), 3);
+    assert!(matches!(
+        &got[0],
+        lsp_types::DocumentChangeOperation::Op(lsp_types::ResourceOp::Create(_))
+    ));
+    assert_eq!(edits(&got[1]), vec![(0, 0, text.to_string())]);
+    assert_eq!(edits(&got[2]), removes);
+    assert!(move_decl::move_decl(&p, &decl, &file).is_err());
+}
+
+#[test]
+fn extract_constructor() {
+    let file = path_to_abs("./tests/extract_constructor.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let extract = |line: u32, col: u32| {
+        let mut actions = Vec::new();
+        extract_constructor::extract_constructor_actions(
+            &p,
+            &file,
+            &action_params(&file, line, col).range,
+            &mut actions,
+        );
+        action_edits(actions)
+    };
+    let r = |line: u32, start: u32, end: u32| Range {
+        start: Position::new(line, start),
+        end: Position::new(line, end),
+    };
+    assert_eq!(
+        extract(4, 24),
+        vec![(
+            "Extract into constructor `extracted`".to_string(),
+            vec![
+                (
+                    r(4, 0, 0),
+                    "(decl extracted (u32 u32) u32)\n(rule (extracted x y) (add x y))\n"
+                        .to_string()
+                ),
+                (r(4, 23, 32), "(extracted x y)".to_string()),
+            ]
+        )]
+    );
+    // `z` is bound by the `let`.
+    assert_eq!(
+        extract(4, 34),
+        vec![(
+            "Extract into constructor `extracted`".to_string(),
+            vec![
+                (
+                    r(4, 0, 0),
+                    "(decl extracted (u32) u32)\n(rule (extracted y) (let ((z u32 (add y 1))) z))\n"
+                        .to_string()
+                ),
+                (r(4, 33, 60), "(extracted y)".to_string()),
+            ]
+        )]
+    );
+    assert_eq!(extract(4, 8).len(), 0);
+    // the term of a `if-let` stays pure and partial.
+    assert_eq!(
+        extract(8, 28),
+        vec![(
+            "Extract into constructor `extracted`".to_string(),
+            vec![
+                (
+                    r(8, 0, 0),
+                    "(decl pure partial extracted (u32) u32)\n(rule (extracted x) (checked x))\n"
+                        .to_string()
+                ),
+                (r(8, 27, 38), "(extracted x)".to_string()),
+            ]
+        )]
+    );
+    // nested in the expression of a `if-let`.
+    assert_eq!(
+        extract(12, 38),
+        vec![(
+            "Extract into constructor `extracted`".to_string(),
+            vec![
+                (
+                    r(12, 0, 0),
+                    "(decl pure extracted (u32) u32)\n(rule (extracted x) (inc x))\n".to_string()
+                ),
+                (r(12, 36, 43), "(extracted x)".to_string()),
+            ]
+        )]
+    );
+}
+
+#[test]
+fn expand_rule() {
+    let file = path_to_abs("./tests/expand_rule.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let got = expand_rule::expand_rule(&p, &file, Position::new(17, 3)).unwrap();
+    assert_eq!(got.rule.range.start.line, 16);
+    // `iconst` is expanded and `reg_to_value` is inserted.
+    assert_eq!(
+        got.text,
+        "(rule 0 (lower (iconst_raw k:u32) (Op.Add y:Reg))\n  (if-let z:Reg (value_reg k:u32))\n  (add_reg y:Reg (reg_to_value z:Reg)))\n"
+    );
+    assert!(expand_rule::expand_rule(&p, &file, Position::new(0, 3)).is_err());
+}
@@ -1,9 +1,16 @@
 use crate::utils::GetPosAndLength;
 use crate::utils::GetPosition;
+use crate::utils::{expr_pos, pattern_pos};
 
+use super::config::InlayHintsConfig;
 use super::context::*;
+use super::converter::{implicit_conversions, term_types};
 use super::item::*;
 use super::project::*;
+use crate::sexpr::{parse_sexprs, pos_to_position, rule_prio, RulePrio, SExpr, SExprKind};
+use cranelift_isle::ast::{Expr, Ident, Pattern, Rule};
+use cranelift_isle::lexer::Pos;
+use std::collections::HashMap;
 
 use lsp_server::*;
 use lsp_types::*;
@@ -12,13 +19,12 @@ use lsp_types::*;
 pub fn on_inlay_hints(context: &Context, request: &Request) {
     let parameters = serde_json::from_value::<InlayHintParams>(request.params.clone())
         .expect("could not deserialize go-to-def request");
-    let fpath = parameters.text_document.uri.clone();
-    let mut handler = Handler::new(fpath.clone(), parameters.clone().range);
-    let _ = context.project.run_visitor_for_file(
-        &parameters.text_document.uri.to_file_path().unwrap(),
-        &mut handler,
-    );
-    let hints = Some(handler.reuslts);
+    let hints = Some(inlay_hints(
+        &context.project,
+        parameters.text_document.uri,
+        parameters.range,
+        &context.config.inlay_hints,
+    ));
     let r = Response::new_ok(request.id.clone(), serde_json::to_value(hints).unwrap());
     context
         .connection
@@ -27,6 +33,26 @@ pub fn on_inlay_hints(context: &Context, request: &Request) {
         .unwrap();
 }
 
+/// Hints of `fpath` in `range` enabled by `config`.
+pub(crate) fn inlay_hints(
+    p: &Project,
+    fpath: Url,
+    range: Range,
+    config: &InlayHintsConfig,
+) -> Vec<InlayHint> {
+    let mut handler = Handler::new(fpath.clone(), range);
+    if config.variable_types {
+        p.run_visitor_for_file(&fpath.to_file_path().unwrap(), &mut handler);
+    }
+    if config.implicit_conversions {
+        handler.converters(p);
+    }
+    if config.argument_types || config.return_types || config.rule_priorities {
+        handler.ast_hints(p, config);
+    }
+    handler.reuslts
+}
+
 struct Handler {
     range: Location,
     reuslts: Vec<InlayHint>,
@@ -40,6 +66,69 @@ impl Handler {
         }
     }
 
+    /// `⟨term⟩` where `ISLE` insert a converter.
+    fn converters(&mut self, p: &Project) {
+        let fpath = self.range.uri.to_file_path().unwrap();
+        for c in implicit_conversions(p, &fpath).into_iter() {
+            if let Some(l) = self.in_range(p, &(c.pos, 0u32)) {
+                let mut hint = mk_inlay_hits(
+                    l.range.start,
+                    goto_inlay_hints_label_parts(
+                        format!("⟨{}⟩", c.term.0),
+                        &c.term.0,
+                        format!("implicit convert {} to {}", c.inner, c.outer),
+                        p,
+                    ),
+                    Some(InlayHintKind::PARAMETER),
+                );
+                hint.padding_left = Some(false);
+                self.reuslts.push(hint);
+            }
+        }
+    }
+
+    /// Hints need walk the `AST`,argument types,return types and priorities of rules.
+    fn ast_hints(&mut self, p: &Project, config: &InlayHintsConfig) {
+        let fpath = self.range.uri.to_file_path().unwrap();
+        let provider = match p.found_file_defs(&fpath) {
+            Some(x) => x,
+            None => return,
+        };
+        let content = p.file_text(&fpath).unwrap_or_default();
+        let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).unwrap_or_default();
+        // end of lists,indexed by line and col of the start.
+        let mut ends = HashMap::new();
+        fn collect_ends(e: &SExpr, ends: &mut HashMap<(usize, usize), Position>) {
+            if let SExprKind::List(children) = &e.kind {
+                ends.insert((e.start.line, e.start.col), pos_to_position(&e.end));
+                for c in children.iter() {
+                    collect_ends(c, ends);
+                }
+            }
+        }
+        for f in forms.iter() {
+            collect_ends(f, &mut ends);
+        }
+        let mut w = AstHints {
+            handler: self,
+            p,
+            config,
+            ends,
+        };
+        provider.with_rule(|r| {
+            if config.rule_priorities && r.prio.is_none() {
+                w.prio(r, &forms);
+            }
+            w.pattern(&r.pattern);
+            for i in r.iflets.iter() {
+                w.pattern(&i.pattern);
+                w.expr(&i.expr);
+            }
+            w.expr(&r.expr);
+        });
+        provider.with_extractor(|e| w.pattern(&e.template));
+    }
+
     fn in_range<T: GetPosAndLength>(&self, project: &Project, pos: &T) -> Option<Location> {
         let l = project.mk_location(pos);
         if let Some(l) = l {
@@ -54,6 +143,134 @@ impl Handler {
     }
 }
 
+struct AstHints<'a> {
+    handler: &'a mut Handler,
+    p: &'a Project,
+    config: &'a InlayHintsConfig,
+    ends: HashMap<(usize, usize), Position>,
+}
+
+impl<'a> AstHints<'a> {
+    fn push(&mut self, pos: Pos, label: InlayHintLabel, kind: InlayHintKind, left: bool) {
+        if let Some(l) = self.p.mk_location(&(pos, 0u32)) {
+            self.push_at(l.range.start, label, Some(kind), left);
+        }
+    }
+
+    /// Like `push` but at a position of this file.
+    fn push_at(
+        &mut self,
+        position: Position,
+        label: InlayHintLabel,
+        kind: Option<InlayHintKind>,
+        left: bool,
+    ) {
+        let l = Location {
+            uri: self.handler.range.uri.clone(),
+            range: Range {
+                start: position,
+                end: position,
+            },
+        };
+        if (&l as &dyn GetPosition).in_range(&self.handler.range) {
+            let mut hint = mk_inlay_hits(position, label, kind);
+            hint.padding_left = Some(left);
+            hint.padding_right = Some(!left);
+            self.handler.reuslts.push(hint);
+        }
+    }
+
+    /// `ty:` before `arg_pos` and `ty` after the term start at `pos`.
+    fn term(&mut self, sym: &Ident, pos: Pos, arg_pos: Vec<Pos>) {
+        let (ret_ty, arg_tys) = match term_types(self.p, sym) {
+            Some(x) => x,
+            None => return,
+        };
+        if self.config.argument_types {
+            for (ty, arg) in arg_tys.iter().zip(arg_pos) {
+                let label =
+                    goto_inlay_hints_label_parts(format!("{}:", ty), ty, ty.clone(), self.p);
+                self.push(arg, label, InlayHintKind::PARAMETER, false);
+            }
+        }
+        if self.config.return_types {
+            if let Some(end) = self.ends.get(&(pos.line, pos.col)).cloned() {
+                self.push_at(
+                    end,
+                    ty_inlay_hints_label_parts(&ret_ty, self.p),
+                    Some(InlayHintKind::TYPE),
+                    true,
+                );
+            }
+        }
+    }
+
+    fn pattern(&mut self, pat: &Pattern) {
+        match pat {
+            Pattern::Term { sym, args, pos } => {
+                self.term(sym, *pos, args.iter().map(pattern_pos).collect());
+                for a in args.iter() {
+                    self.pattern(a);
+                }
+            }
+            Pattern::BindPattern { subpat, .. } => self.pattern(subpat.as_ref()),
+            Pattern::And { subpats, .. } => {
+                for s in subpats.iter() {
+                    self.pattern(s);
+                }
+            }
+            Pattern::Var { .. }
+            | Pattern::ConstInt { .. }
+            | Pattern::ConstPrim { .. }
+            | Pattern::Wildcard { .. }
+            | Pattern::MacroArg { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Term { sym, args, pos } => {
+                self.term(sym, *pos, args.iter().map(expr_pos).collect());
+                for a in args.iter() {
+                    self.expr(a);
+                }
+            }
+            Expr::Let { defs, body, .. } => {
+                for d in defs.iter() {
+                    self.expr(d.val.as_ref());
+                }
+                self.expr(body.as_ref());
+            }
+            Expr::Var { .. } | Expr::ConstInt { .. } | Expr::ConstPrim { .. } => {}
+        }
+    }
+
+    /// `0` where a explicit priority would be written.
+    fn prio(&mut self, r: &Rule, forms: &[SExpr]) {
+        let form = match forms.iter().find(|x| x.contains_pos(&r.pos)) {
+            Some(x) => x,
+            None => return,
+        };
+        let after = match rule_prio(form) {
+            Some(RulePrio::After(x)) => x,
+            _ => return,
+        };
+        self.push_at(
+            pos_to_position(&after.end),
+            InlayHintLabel::LabelParts(vec![InlayHintLabelPart {
+                value: "0".to_string(),
+                tooltip: Some(InlayHintLabelPartTooltip::String(
+                    "priority,default is 0".to_string(),
+                )),
+                location: None,
+                command: None,
+            }]),
+            None,
+            true,
+        );
+    }
+}
+
 impl ItemOrAccessHandler for Handler {
     fn handle_item_or_access(&mut self, p: &Project, item: &ItemOrAccess) {
         match item {
@@ -71,7 +288,7 @@ impl ItemOrAccessHandler for Handler {
                                 character: name_loc.range.end.character,
                             },
                             ty_inlay_hints_label_parts(&ty.0, p),
-                            InlayHintKind::TYPE,
+                            Some(InlayHintKind::TYPE),
                         ));
                     }
                 }
@@ -94,11 +311,11 @@ impl std::fmt::Display for Handler {
     }
 }
 
-fn mk_inlay_hits(pos: Position, label: InlayHintLabel, kind: InlayHintKind) -> InlayHint {
+fn mk_inlay_hits(pos: Position, label: InlayHintLabel, kind: Option<InlayHintKind>) -> InlayHint {
     InlayHint {
         position: pos,
         label,
-        kind: Some(kind),
+        kind,
         text_edits: None,
         tooltip: None,
         padding_left: Some(true),
@@ -146,16 +363,22 @@ impl From<&Location> for PathAndRange {
 }
 
 fn ty_inlay_hints_label_parts(ty: &String, p: &Project) -> InlayHintLabel {
+    goto_inlay_hints_label_parts(ty.clone(), ty, "Go To Definition.".to_string(), p)
+}
+
+/// `label` jump to the definition of `name` when clicked.
+fn goto_inlay_hints_label_parts(
+    label: String,
+    name: &String,
+    tooltip: String,
+    p: &Project,
+) -> InlayHintLabel {
     InlayHintLabel::LabelParts(vec![InlayHintLabelPart {
-        value: ty.clone(),
-        tooltip: Some(InlayHintLabelPartTooltip::String(
-            "Go To Definition.".to_string(),
-        )),
+        value: label,
+        tooltip: Some(InlayHintLabelPartTooltip::String(tooltip)),
         location: None,
-        command: if let Some(loc) = p.mk_location(&p.context.query_item_clone(ty).def_loc()) {
-            Some(ISLEAnalyzerClientCommands::GotoDefinition(loc).to_lsp_command())
-        } else {
-            None
-        },
+        command: p
+            .mk_location(&p.context.query_item_clone(name).def_loc())
+            .map(|loc| ISLEAnalyzerClientCommands::GotoDefinition(loc).to_lsp_command()),
     }])
 }
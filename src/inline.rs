@@ -0,0 +1,378 @@
+//! Inline a extractor macro at a use site in a pattern,
+//! or a constructor with one unconditional rule at a use site in a expression.
+//! The definition can be removed too if there is no other use.
+
+use super::code_action::decl_name;
+use super::converter::term_types;
+use super::project::*;
+use crate::sexpr::{enclosing, parse_sexprs, position_to_offset, splice, SExpr};
+use cranelift_isle::ast::{Expr, Ident, Pattern, Rule};
+use cranelift_isle::lexer::Pos;
+use lsp_types::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Site {
+    Pattern,
+    Expr,
+}
+
+/// Inline the term applied at `range`.
+pub(crate) fn inline_actions(
+    p: &Project,
+    fpath: &Path,
+    range: &Range,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    let file_index = match p.found_file_index(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let content = match p.file_text(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let forms = match parse_sexprs(content.as_str(), fpath.to_str().unwrap()) {
+        Ok(x) => x,
+        Err(_) => return,
+    };
+    let candidates = enclosing(&forms, position_to_offset(content.as_str(), &range.start));
+    let form = match candidates.first() {
+        Some(x) if x.head() == Some("rule") => *x,
+        _ => return,
+    };
+    let mut sites = Vec::new();
+    if let Some(provider) = p.found_file_defs(fpath) {
+        provider.with_rule(|r| {
+            if r.pos.file == file_index && form.contains_pos(&r.pos) {
+                rule_sites(r, &mut sites);
+            }
+        });
+    }
+    // innermost first.
+    let (name, site, sexpr) = match candidates.iter().rev().find_map(|c| {
+        sites
+            .iter()
+            .find(|(_, pos, _)| (pos.line, pos.col) == (c.start.line, c.start.col))
+            .map(|(name, _, site)| (name.clone(), *site, *c))
+    }) {
+        Some(x) => x,
+        None => return,
+    };
+    let args: Vec<_> = sexpr.children()[1..]
+        .iter()
+        .map(|x| x.text(content.as_str()).to_string())
+        .collect();
+    let inlined = match site {
+        Site::Pattern => inline_extractor(p, &name, &args),
+        Site::Expr => inline_constructor(p, &name, &sexpr.children()[1..], &args),
+    };
+    let (def, new_text) = match inlined {
+        Some(x) => x,
+        None => return,
+    };
+    let uri = Url::from_file_path(fpath).unwrap();
+    let replace = TextEdit {
+        range: sexpr.range(),
+        new_text,
+    };
+    let mut changes = HashMap::new();
+    changes.insert(uri.clone(), vec![replace.clone()]);
+    actions.push(inline_action(format!("Inline `{}`", name), changes));
+
+    let removes = match definition_ranges(p, &name, &def) {
+        Some(x) => x,
+        None => return,
+    };
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    changes.insert(uri, vec![replace]);
+    for loc in removes.into_iter() {
+        changes.entry(loc.uri).or_default().push(TextEdit {
+            range: loc.range,
+            new_text: String::new(),
+        });
+    }
+    actions.push(inline_action(
+        format!("Inline `{}` and remove its definition", name),
+        changes,
+    ));
+}
+
+fn inline_action(title: String, changes: HashMap<Url, Vec<TextEdit>>) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(CodeActionKind::REFACTOR_INLINE),
+        edit: Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Terms applied in `r`,the root term is not.
+fn rule_sites(r: &Rule, sites: &mut Vec<(String, Pos, Site)>) {
+    fn pattern(pat: &Pattern, sites: &mut Vec<(String, Pos, Site)>) {
+        match pat {
+            Pattern::Term { sym, args, pos } => {
+                sites.push((sym.0.clone(), *pos, Site::Pattern));
+                args.iter().for_each(|x| pattern(x, sites));
+            }
+            Pattern::BindPattern { subpat, .. } => pattern(subpat.as_ref(), sites),
+            Pattern::And { subpats, .. } => subpats.iter().for_each(|x| pattern(x, sites)),
+            Pattern::Var { .. }
+            | Pattern::ConstInt { .. }
+            | Pattern::ConstPrim { .. }
+            | Pattern::Wildcard { .. }
+            | Pattern::MacroArg { .. } => {}
+        }
+    }
+    fn expr(e: &Expr, sites: &mut Vec<(String, Pos, Site)>) {
+        match e {
+            Expr::Term { sym, args, pos } => {
+                sites.push((sym.0.clone(), *pos, Site::Expr));
+                args.iter().for_each(|x| expr(x, sites));
+            }
+            Expr::Let { defs, body, .. } => {
+                defs.iter().for_each(|x| expr(x.val.as_ref(), sites));
+                expr(body.as_ref(), sites);
+            }
+            Expr::Var { .. } | Expr::ConstInt { .. } | Expr::ConstPrim { .. } => {}
+        }
+    }
+    if let Pattern::Term { args, .. } = &r.pattern {
+        args.iter().for_each(|x| pattern(x, sites));
+    }
+    for i in r.iflets.iter() {
+        pattern(&i.pattern, sites);
+        expr(&i.expr, sites);
+    }
+    expr(&r.expr, sites);
+}
+
+/// The top-level form at `pos`.
+fn def_form(p: &Project, pos: &Pos) -> Option<(String, SExpr)> {
+    let fpath = p.file_index_path(pos.file)?;
+    let content = p.file_text(&fpath)?;
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).ok()?;
+    let form = forms.into_iter().find(|x| x.contains_pos(pos))?;
+    Some((content, form))
+}
+
+/// Template of the extractor with macro arguments replaced by `args`,
+/// and the position of the extractor.
+fn inline_extractor(p: &Project, name: &str, args: &[String]) -> Option<(Pos, String)> {
+    let mut pos = None;
+    ProjectAstProvider::new(p).with_extractor(|x| {
+        if x.term.0 == name {
+            pos = Some(x.pos);
+        }
+    });
+    let pos = pos?;
+    let (content, form) = def_form(p, &pos)?;
+    let children = form.children();
+    let params: Vec<_> = children.get(1)?.children()[1..]
+        .iter()
+        .filter_map(|x| x.atom())
+        .collect();
+    if params.len() != args.len() || has_duplicates(&params) {
+        return None;
+    }
+    let template = children.get(2)?;
+    // `_` for a parameter appears twice would lose the equality.
+    if params
+        .iter()
+        .zip(args.iter())
+        .any(|(param, arg)| arg == "_" && count_atoms(template, param) > 1)
+    {
+        return None;
+    }
+    let map = params
+        .into_iter()
+        .map(|x| x.to_string())
+        .zip(args.iter().cloned())
+        .collect();
+    Some((pos, subst(content.as_str(), template, &map)))
+}
+
+/// Body of the only rule of `name` with parameters bound to `args`.
+fn inline_constructor(
+    p: &Project,
+    name: &str,
+    arg_exprs: &[SExpr],
+    args: &[String],
+) -> Option<(Pos, String)> {
+    let mut rules = Vec::new();
+    ProjectAstProvider::new(p).with_rule(|r| {
+        if get_rule_target(&r.pattern).map(|(x, _)| x.as_str()) == Some(name) {
+            rules.push(r.clone());
+        }
+    });
+    if rules.len() != 1 || !rules[0].iflets.is_empty() {
+        return None;
+    }
+    let r = &rules[0];
+    let params = match &r.pattern {
+        Pattern::Term { args, .. } => args
+            .iter()
+            .map(|x| match x {
+                Pattern::Var { var, .. } => Some(Some(var.0.clone())),
+                Pattern::Wildcard { .. } => Some(None),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?,
+        _ => return None,
+    };
+    // a parameter appears twice is a equality constraint on the arguments.
+    if params.len() != args.len() || has_duplicates(&params.iter().flatten().collect::<Vec<_>>()) {
+        return None;
+    }
+    let (content, form) = def_form(p, &r.pos)?;
+    let body = form.children().last()?;
+    if count_atoms(body, name) > 0 {
+        // recursive.
+        return None;
+    }
+    let mut atoms = Vec::new();
+    collect_atoms(body, &mut atoms);
+    // names of the caller,a name bound by `let` must not capture them.
+    let mut arg_atoms = Vec::new();
+    for x in arg_exprs.iter() {
+        match x.atom() {
+            Some(x) => arg_atoms.push(x),
+            None => collect_atoms(x, &mut arg_atoms),
+        }
+    }
+    // `base` itself may be in the body if it is the parameter.
+    let fresh = |base: &str, param: bool| {
+        let mut name = base.to_string();
+        let mut index = 1;
+        while arg_atoms.contains(&name.as_str())
+            || (!(param && name == base) && atoms.contains(&name.as_str()))
+        {
+            name = format!("{}{}", base, index);
+            index += 1;
+        }
+        name
+    };
+    let has_let = has_let(body);
+    let (_, tys) = term_types(p, &Ident(name.to_string(), r.pos))?;
+    let mut map = HashMap::new();
+    let mut lets = Vec::new();
+    for (index, (param, arg)) in params.iter().zip(args.iter()).enumerate() {
+        let is_atom = arg_exprs[index].atom().is_some();
+        let ty = tys.get(index)?;
+        match param {
+            Some(param) if param == arg => {}
+            Some(param)
+                if !has_let
+                    && (is_atom || atoms.iter().filter(|x| **x == param.as_str()).count() == 1) =>
+            {
+                map.insert(param.clone(), arg.clone());
+            }
+            Some(param) => {
+                let var = fresh(param, true);
+                if &var != param {
+                    map.insert(param.clone(), var.clone());
+                }
+                lets.push(format!("({} {} {})", var, ty, arg));
+            }
+            None if is_atom => {}
+            // keep the evaluation.
+            None => lets.push(format!(
+                "({} {} {})",
+                fresh(&format!("_arg{}", index), false),
+                ty,
+                arg
+            )),
+        }
+    }
+    let body = subst(content.as_str(), body, &map);
+    let text = if !lets.is_empty() {
+        format!("(let ({}) {})", lets.join(" "), body)
+    } else {
+        body
+    };
+    Some((r.pos, text))
+}
+
+/// `e` with atoms in `map` replaced,heads of lists are not.
+fn subst(content: &str, e: &SExpr, map: &HashMap<String, String>) -> String {
+    match e.atom() {
+        Some(x) => map.get(x).cloned().unwrap_or(x.to_string()),
+        None => splice(content, e, |index, c| {
+            if index == 0 && c.atom().is_some() {
+                c.text(content).to_string()
+            } else {
+                subst(content, c, map)
+            }
+        }),
+    }
+}
+
+/// Atoms of `e` not at the head of a list.
+fn collect_atoms<'a>(e: &'a SExpr, atoms: &mut Vec<&'a str>) {
+    for (index, c) in e.children().iter().enumerate() {
+        match c.atom() {
+            Some(x) if index > 0 => atoms.push(x),
+            Some(_) => {}
+            None => collect_atoms(c, atoms),
+        }
+    }
+}
+
+/// How many times `name` appears in `e`.
+fn count_atoms(e: &SExpr, name: &str) -> usize {
+    match e.atom() {
+        Some(x) => (x == name) as usize,
+        None => e.children().iter().map(|x| count_atoms(x, name)).sum(),
+    }
+}
+
+fn has_duplicates<T: PartialEq>(xs: &[T]) -> bool {
+    xs.iter()
+        .enumerate()
+        .any(|(index, x)| xs[index + 1..].contains(x))
+}
+
+fn has_let(e: &SExpr) -> bool {
+    e.head() == Some("let") || e.children().iter().any(has_let)
+}
+
+/// Whole lines of the decl of `name` and the definition at `def`,
+/// `None` if `name` is used anywhere else.
+fn definition_ranges(p: &Project, name: &str, def: &Pos) -> Option<Vec<Location>> {
+    let mut ret = Vec::new();
+    let mut uses = 0;
+    for fpath in p.mk_file_paths().into_iter() {
+        let content = match p.file_text(&fpath) {
+            Some(x) => x,
+            None => continue,
+        };
+        let forms = match parse_sexprs(content.as_str(), fpath.to_str().unwrap()) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        let is_def_file = p.found_file_index(&fpath) == Some(def.file);
+        for f in forms.iter() {
+            if decl_name(f) == Some(name) || (is_def_file && f.contains_pos(def)) {
+                ret.push(Location {
+                    uri: Url::from_file_path(&fpath).unwrap(),
+                    range: Range {
+                        start: Position::new(f.start_line(), 0),
+                        end: Position::new(f.end_line() + 1, 0),
+                    },
+                });
+                continue;
+            }
+            uses += count_atoms(f, name);
+        }
+    }
+    // the one being inlined.
+    if uses == 1 {
+        Some(ret)
+    } else {
+        None
+    }
+}
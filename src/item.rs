@@ -55,6 +55,19 @@ pub enum Item {
     EnumVariant {
         v: Variant,
     },
+    /// A integer literal like `1` or `0xff`.
+    ConstInt {
+        /// The literal as written in source.
+        lit: Ident,
+        val: i128,
+        /// The type expected by the enclosing term.
+        ty: Ident,
+    },
+    /// The Rust function bound by `(extern constructor term func)`
+    /// or `(extern extractor term func)`.
+    ExternFunc {
+        name: Ident,
+    },
 }
 
 impl Default for Item {
@@ -77,14 +90,17 @@ lazy_static! {
 impl Item {
     pub(crate) fn def_loc(&self) -> (Pos, u32) {
         match self {
-            Item::Type { ty } => (ty.pos, ty.name.0.len() as u32),
+            Item::Type { ty } => (ty.name.1, ty.name.0.len() as u32),
             Item::Decl { decl, kind: _ } => (decl.term.1, decl.term.0.len() as u32),
             Item::Dummy => (UNKNOWN_POS, 0),
-            Item::Const { name, ty: _ } => (name.1, 0),
+            // `name` not include the leading `$`.
+            Item::Const { name, ty: _ } => (name.1, name.0.len() as u32 + 1),
             Item::Var { name, ty: _, .. } => (name.1, name.0.len() as u32),
             Item::EnumMemberName { name } => (name.1, name.0.len() as u32),
             Item::EnumMemberField { name } => (name.1, name.0.len() as u32),
             Item::EnumVariant { v } => (v.name.1, v.name.0.len() as u32),
+            Item::ConstInt { lit, .. } => (lit.1, lit.0.len() as u32),
+            Item::ExternFunc { name } => (name.1, name.0.len() as u32),
         }
     }
 
@@ -101,8 +117,10 @@ pub struct DeclKind(pub(crate) u8);
 impl DeclKind {
     pub(crate) const EXTRATOR: u8 = 1;
     pub(crate) const CONSTRUCTOR: u8 = 2;
+    /// Implemented by Rust code.
+    pub(crate) const EXTERN: u8 = 4;
     pub(crate) fn has(self, x: u8) -> bool {
-        (self.0 | x) != 0
+        (self.0 & x) != 0
     }
 }
 
@@ -176,6 +194,8 @@ impl std::fmt::Display for Item {
             Item::EnumMemberName { name } => write!(f, "enum_member:{}", name.0.as_str()),
             Item::EnumMemberField { name } => write!(f, "enum_field:{}", name.0.as_str()),
             Item::EnumVariant { v } => write!(f, "enum_variant:{}", v.name.0),
+            Item::ConstInt { lit, .. } => write!(f, "const_int:{}", lit.0.as_str()),
+            Item::ExternFunc { name } => write!(f, "extern_func:{}", name.0.as_str()),
         }
     }
 }
@@ -187,3 +207,9 @@ impl std::fmt::Display for Access {
         write!(f, "{} {}->{}", self.kind.to_static_str(), access.0, def)
     }
 }
+
+/// Written for what can't be inferred,like a unknown type or a new argument.
+pub(crate) const PLACEHOLDER: &str = "TODO";
+
+/// Name of a extracted item,a number is appended if it is taken.
+pub(crate) const NEW_ITEM_NAME: &str = "extracted";
@@ -4,24 +4,45 @@ extern crate lazy_static;
 use lsp_types::{Location, Position, Range};
 use utils::GetPosAndLength;
 
+pub mod change_signature;
+pub mod code_action;
 pub mod comment;
 pub mod completion;
+pub mod config;
 pub mod context;
+pub(crate) mod converter;
+pub mod dead_code;
+pub mod document_highlight;
 pub mod document_symbol;
+pub mod expand_rule;
+pub mod extract_constructor;
+pub mod extract_extractor;
 pub mod fmt;
+pub mod folding_range;
 pub mod goto_definition;
 pub mod hover;
 #[cfg(test)]
 pub mod ide_test;
 pub mod inlay_hitnt;
+pub mod inline;
 pub mod item;
+pub mod lint;
+pub mod missing_impl;
+pub mod move_decl;
+pub mod overlap;
+pub mod prio_fix;
 pub mod project;
 pub mod project_visit;
 pub mod references;
 pub mod reload;
 pub mod rename;
+pub(crate) mod rust_scan;
+pub mod selection_range;
+pub(crate) mod sema_info;
 pub mod semantic_tokens;
+pub(crate) mod sexpr;
 pub mod show_rust_code;
+pub mod unused_var;
 pub mod utils;
 use std::collections::HashSet;
 
@@ -0,0 +1,161 @@
+//! Lints run by the analyzer over a whole `Project`,
+//! beyond the errors `cranelift_isle::compile` reports.
+//!
+//! A lint can be silenced for the form following a comment like
+//! `;; isle-analyzer: allow(lint-name)`.
+
+use super::comment::CommentExtrator;
+use super::config::ServerConfig;
+use super::dead_code::UnusedItems;
+use super::missing_impl::MissingImpl;
+use super::project::*;
+use super::unused_var::UnusedVariables;
+use crate::sexpr::parse_sexprs;
+use lsp_types::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Allow,
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+impl LintLevel {
+    fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            LintLevel::Allow => None,
+            LintLevel::Hint => Some(DiagnosticSeverity::HINT),
+            LintLevel::Info => Some(DiagnosticSeverity::INFORMATION),
+            LintLevel::Warning => Some(DiagnosticSeverity::WARNING),
+            LintLevel::Error => Some(DiagnosticSeverity::ERROR),
+        }
+    }
+}
+
+pub struct LintResult {
+    pub loc: Location,
+    pub message: String,
+    /// Like `DiagnosticTag::UNNECESSARY`.
+    pub tags: Vec<DiagnosticTag>,
+}
+
+pub trait Lint {
+    /// Name used in configuration and `allow(...)`.
+    fn name(&self) -> &'static str;
+    fn default_level(&self) -> LintLevel;
+    fn check(&self, p: &Project, results: &mut Vec<LintResult>);
+}
+
+/// All lints the analyzer knows.
+pub fn lints() -> Vec<Box<dyn Lint>> {
+    vec![
+        Box::new(UnusedItems),
+        Box::new(MissingImpl),
+        Box::new(UnusedVariables),
+    ]
+}
+
+/// Run `lints` over `p`,diagnostics of every file in `p` are returned,
+/// a file without any is mapped to a empty `Vec`.
+pub fn lint_diagnostics(
+    p: &Project,
+    config: &ServerConfig,
+    lints: &[Box<dyn Lint>],
+) -> HashMap<PathBuf, Vec<Diagnostic>> {
+    let mut ret: HashMap<PathBuf, Vec<Diagnostic>> = p
+        .mk_file_paths()
+        .into_iter()
+        .map(|x| (x, Vec::new()))
+        .collect();
+    let mut allows = HashMap::new();
+    for lint in lints.iter() {
+        let level = config
+            .lints
+            .get(lint.name())
+            .cloned()
+            .unwrap_or(lint.default_level());
+        let severity = match level.severity() {
+            Some(x) => x,
+            None => continue,
+        };
+        let mut results = Vec::new();
+        lint.check(p, &mut results);
+        for r in results.into_iter() {
+            let fpath = match r.loc.uri.to_file_path() {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            let allow = allows
+                .entry(fpath.clone())
+                .or_insert_with(|| allowed_lines(p, &fpath));
+            if allow.iter().any(|(name, start, end)| {
+                name == lint.name() && (*start..=*end).contains(&r.loc.range.start.line)
+            }) {
+                continue;
+            }
+            ret.entry(fpath).or_default().push(Diagnostic {
+                range: r.loc.range,
+                severity: Some(severity),
+                code: Some(NumberOrString::String(lint.name().to_string())),
+                source: Some("isle-analyzer".to_string()),
+                message: r.message,
+                tags: if !r.tags.is_empty() {
+                    Some(r.tags)
+                } else {
+                    None
+                },
+                ..Default::default()
+            });
+        }
+    }
+    ret
+}
+
+/// Lint names of `;; isle-analyzer: allow(...)` comments in `fpath`
+/// and the zero-based lines of the form each one applies to.
+fn allowed_lines(p: &Project, fpath: &PathBuf) -> Vec<(String, u32, u32)> {
+    let content = match p.file_text(fpath) {
+        Some(x) => x,
+        None => return vec![],
+    };
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).unwrap_or_default();
+    let mut ret = Vec::new();
+    for c in CommentExtrator::new(content.as_str()).comments().iter() {
+        let names = match parse_allow(c.content.as_str()) {
+            Some(x) => x,
+            None => continue,
+        };
+        // the form on the same line or the next one.
+        let form = forms
+            .iter()
+            .find(|f| f.start_line() <= c.line && c.line <= f.end_line())
+            .or_else(|| forms.iter().find(|f| f.start_line() > c.line));
+        let (start, end) = match form {
+            Some(f) => (f.start_line(), f.end_line()),
+            None => continue,
+        };
+        for name in names.into_iter() {
+            ret.push((name, start, end));
+        }
+    }
+    ret
+}
+
+/// `isle-analyzer: allow(a, b)` to `a` and `b`.
+pub(crate) fn parse_allow(comment: &str) -> Option<Vec<String>> {
+    let x = comment.trim().strip_prefix("isle-analyzer:")?.trim();
+    let x = x.strip_prefix("allow")?.trim();
+    let x = x.strip_prefix('(')?.strip_suffix(')')?;
+    Some(
+        x.split(',')
+            .map(|x| x.trim().to_string())
+            .filter(|x| !x.is_empty())
+            .collect(),
+    )
+}
@@ -0,0 +1,121 @@
+//! Decls with neither rules nor a extern binding,
+//! which only fail in codegen or when the generated Rust code is compiled.
+
+use super::dead_code::{dead_code, DeadCodeKind};
+use super::item::*;
+use super::lint::*;
+use super::project::*;
+use cranelift_isle::ast::*;
+use std::collections::HashSet;
+
+/// Report decls without any implementation,
+/// naming the missing one according to how the term is used.
+/// Unused decls are left to `UnusedItems`.
+pub struct MissingImpl;
+
+impl Lint for MissingImpl {
+    fn name(&self) -> &'static str {
+        "missing-impl"
+    }
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warning
+    }
+    fn check(&self, p: &Project, results: &mut Vec<LintResult>) {
+        let provider = ProjectAstProvider::new(p);
+        let mut uses = Uses::default();
+        provider.with_rule(|r| {
+            // the root of the rule is a implementation.
+            match &r.pattern {
+                Pattern::Term { args, .. } => args.iter().for_each(|x| uses.pattern(x)),
+                _ => uses.pattern(&r.pattern),
+            }
+            for i in r.iflets.iter() {
+                uses.pattern(&i.pattern);
+                uses.expr(&i.expr);
+            }
+            uses.expr(&r.expr);
+        });
+        provider.with_extractor(|e| uses.pattern(&e.template));
+        let dead: Vec<_> = dead_code(p)
+            .into_iter()
+            .filter(|x| x.kind == DeadCodeKind::Decl)
+            .map(|x| x.location)
+            .collect();
+        provider.with_decl(|d| {
+            match p.context.query_item_clone(&d.term.0) {
+                Item::Decl { kind, .. } if kind.0 == 0 => {}
+                _ => return,
+            }
+            let name = &d.term.0;
+            let extractor = uses.extractors.contains(name);
+            let constructor = uses.constructors.contains(name);
+            let message = match (extractor, constructor) {
+                (false, false) => format!(
+                    "`{}` has no rules,extractor or extern binding",
+                    name
+                ),
+                (true, false) => format!(
+                    "`{}` is used as a extractor but has no `(extractor ({} ...) ...)` or `(extern extractor {} ...)`",
+                    name, name, name
+                ),
+                (false, true) => format!(
+                    "`{}` is used as a constructor but has no `(rule ({} ...) ...)` or `(extern constructor {} ...)`",
+                    name, name, name
+                ),
+                (true, true) => format!(
+                    "`{}` is used as a extractor and a constructor but has neither implementation",
+                    name
+                ),
+            };
+            if let Some(loc) = p.mk_location(&d.term) {
+                if dead.contains(&loc) {
+                    return;
+                }
+                results.push(LintResult {
+                    loc,
+                    message,
+                    tags: vec![],
+                });
+            }
+        });
+    }
+}
+
+/// Terms applied in patterns and in expressions.
+#[derive(Default)]
+struct Uses {
+    extractors: HashSet<String>,
+    constructors: HashSet<String>,
+}
+
+impl Uses {
+    fn pattern(&mut self, pat: &Pattern) {
+        match pat {
+            Pattern::Term { sym, args, .. } => {
+                self.extractors.insert(sym.0.clone());
+                args.iter().for_each(|x| self.pattern(x));
+            }
+            Pattern::BindPattern { subpat, .. } => self.pattern(subpat.as_ref()),
+            Pattern::And { subpats, .. } => subpats.iter().for_each(|x| self.pattern(x)),
+            Pattern::Var { .. }
+            | Pattern::ConstInt { .. }
+            | Pattern::ConstPrim { .. }
+            | Pattern::Wildcard { .. }
+            | Pattern::MacroArg { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Term { sym, args, .. } => {
+                self.constructors.insert(sym.0.clone());
+                args.iter().for_each(|x| self.expr(x));
+            }
+            Expr::Let { defs, body, .. } => {
+                defs.iter().for_each(|x| self.expr(x.val.as_ref()));
+                self.expr(body.as_ref());
+            }
+            Expr::Var { .. } | Expr::ConstInt { .. } | Expr::ConstPrim { .. } => {}
+        }
+    }
+}
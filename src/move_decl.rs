@@ -0,0 +1,172 @@
+//! Move a decl to another file,with its rules,extractor,externs
+//! and the comments right above them.
+
+use super::change_signature::decl_at;
+use super::context::Context;
+use super::project::*;
+use crate::send_err;
+use crate::sexpr::parse_sexprs;
+use cranelift_isle::ast::{Decl, Def, Extern};
+use cranelift_isle::lexer::Pos;
+use lsp_server::*;
+use lsp_types::*;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Clone, serde::Deserialize)]
+struct Req {
+    fpath: String,
+    line: u32,
+    col: u32,
+    /// The target file,created if it is not in the project.
+    to: String,
+}
+
+/// Handles `isle/move_decl`,the result is a `WorkspaceEdit`.
+pub fn on_move_decl(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<Req>(request.params.clone())
+        .expect("could not deserialize move decl request");
+    let fpath = PathBuf::from_str(parameters.fpath.as_str()).unwrap();
+    let to = PathBuf::from_str(parameters.to.as_str()).unwrap();
+    let decl = match decl_at(&context.project, &fpath, parameters.line, parameters.col) {
+        Some(x) => x,
+        None => {
+            send_err(context, "Not a decl".to_string(), request.id.clone());
+            return;
+        }
+    };
+    match move_decl(&context.project, &decl, &to) {
+        Ok(x) => {
+            let r = Response::new_ok(request.id.clone(), serde_json::to_value(x).unwrap());
+            context
+                .connection
+                .sender
+                .send(Message::Response(r))
+                .unwrap();
+        }
+        Err(err) => send_err(context, err, request.id.clone()),
+    }
+}
+
+/// Positions of the decl,rules,extractor and externs of `name`,the decl first.
+/// The same positions `Project::comments` are indexed by.
+fn definitions(p: &Project, name: &str) -> Vec<Pos> {
+    let mut ret = Vec::new();
+    ProjectAstProvider::new(p).with_def(|d| {
+        let found = match d {
+            Def::Decl(x) => x.term.0 == name,
+            Def::Rule(x) => get_rule_target(&x.pattern).map(|(t, _)| t.as_str()) == Some(name),
+            Def::Extractor(x) => x.term.0 == name,
+            Def::Extern(Extern::Extractor { term, .. })
+            | Def::Extern(Extern::Constructor { term, .. }) => term.0 == name,
+            _ => false,
+        };
+        if let Some(pos) = get_decl_pos(d).filter(|_| found) {
+            ret.push((!matches!(d, Def::Decl(_)), *pos));
+        }
+    });
+    ret.sort_by_key(|(not_decl, pos)| (*not_decl, pos.file, pos.line));
+    ret.into_iter().map(|(_, pos)| pos).collect()
+}
+
+pub(crate) fn move_decl(p: &Project, decl: &Decl, to: &Path) -> Result<WorkspaceEdit, String> {
+    let to_index = p.found_file_index(to);
+    // text moved and lines removed of each file.
+    let mut moved = String::new();
+    let mut removes: HashMap<PathBuf, Vec<TextEdit>> = HashMap::new();
+    let mut files = HashMap::new();
+    for pos in definitions(p, decl.term.0.as_str()).into_iter() {
+        if Some(pos.file) == to_index {
+            continue;
+        }
+        let fpath = match p.file_index_path(pos.file) {
+            Some(x) => x,
+            None => continue,
+        };
+        let (content, forms) = match files.entry(pos.file) {
+            Entry::Occupied(x) => x.into_mut(),
+            Entry::Vacant(x) => {
+                let content = p.file_text(&fpath).unwrap_or_default();
+                let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap())
+                    .map_err(|err| format!("parse {:?} failed:{:?}", fpath, err))?;
+                x.insert((content, forms))
+            }
+        };
+        let form = match forms.iter().find(|x| x.contains_pos(&pos)) {
+            Some(x) => x,
+            None => continue,
+        };
+        let lines: Vec<_> = content.lines().collect();
+        let start = p
+            .comments
+            .get(&fpath)
+            .and_then(|x| x.attached_start(&pos))
+            .unwrap_or(form.start_line()) as usize;
+        let end = form.end_line() as usize;
+        for l in lines[start..=end].iter() {
+            moved.push_str(l);
+            moved.push('\n');
+        }
+        removes.entry(fpath).or_default().push(TextEdit {
+            range: Range {
+                start: Position::new(start as u32, 0),
+                end: Position::new(end as u32 + 1, 0),
+            },
+            new_text: String::new(),
+        });
+    }
+    if moved.is_empty() {
+        return Err(format!("`{}` is already in {:?}", decl.term.0, to));
+    }
+    let edit = |fpath: &Path, edits: Vec<TextEdit>| {
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: Url::from_file_path(fpath).unwrap(),
+                version: None,
+            },
+            edits: edits.into_iter().map(OneOf::Left).collect(),
+        })
+    };
+    let mut ops = Vec::new();
+    let insert = match to_index {
+        Some(_) => {
+            let content = p.file_text(to).unwrap_or_default();
+            let end = Position::new(content.lines().count() as u32, 0);
+            if !content.is_empty() && !content.ends_with('\n') {
+                moved.insert(0, '\n');
+            }
+            TextEdit {
+                range: Range { start: end, end },
+                new_text: format!("\n{}", moved),
+            }
+        }
+        None => {
+            ops.push(DocumentChangeOperation::Op(ResourceOp::Create(
+                CreateFile {
+                    uri: Url::from_file_path(to).map_err(|_| format!("bad path {:?}", to))?,
+                    options: Some(CreateFileOptions {
+                        overwrite: Some(false),
+                        ignore_if_exists: Some(true),
+                    }),
+                    annotation_id: None,
+                },
+            )));
+            TextEdit {
+                range: Range::default(),
+                new_text: moved,
+            }
+        }
+    };
+    ops.push(edit(to, vec![insert]));
+    let mut removes: Vec<_> = removes.into_iter().collect();
+    removes.sort_by(|a, b| a.0.cmp(&b.0));
+    for (fpath, edits) in removes.into_iter() {
+        ops.push(edit(&fpath, edits));
+    }
+    Ok(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(ops)),
+        ..Default::default()
+    })
+}
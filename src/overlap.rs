@@ -0,0 +1,238 @@
+//! Explain a `OverlapError` by a input both rules match.
+//! The input is built from the left-hand side patterns only,
+//! different extractors may match the same value so they are combined with `and`.
+//! A variable bound twice is a equality constraint the input can't show,
+//! no input is given for such rules.
+
+use super::context::Context;
+use super::prio_fix::{conflicts, rule_range};
+use super::project::*;
+use crate::send_err;
+use cranelift_isle::ast::{Def, Pattern, Rule};
+use cranelift_isle::lexer::Pos;
+use lsp_server::*;
+use lsp_types::*;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Witness {
+    Any,
+    /// Value and how it is written.
+    Int(i128, String),
+    Prim(String),
+    Term(String, Vec<Witness>),
+    And(Vec<Witness>),
+}
+
+impl Witness {
+    fn from_pattern(p: &Project, pat: &Pattern) -> Self {
+        match pat {
+            Pattern::Var { .. } | Pattern::Wildcard { .. } | Pattern::MacroArg { .. } => {
+                Witness::Any
+            }
+            Pattern::BindPattern { subpat, .. } => Self::from_pattern(p, subpat.as_ref()),
+            Pattern::ConstInt { val, pos } => Witness::Int(
+                *val,
+                p.atom_text(pos)
+                    .map(|x| x.to_string())
+                    .unwrap_or(val.to_string()),
+            ),
+            Pattern::ConstPrim { val, .. } => Witness::Prim(val.0.clone()),
+            Pattern::Term { sym, args, .. } => Witness::Term(
+                sym.0.clone(),
+                args.iter().map(|x| Self::from_pattern(p, x)).collect(),
+            ),
+            Pattern::And { subpats, .. } => {
+                let mut ret = Witness::Any;
+                for s in subpats.iter() {
+                    ret = match ret.unify(&Self::from_pattern(p, s)) {
+                        Some(x) => x,
+                        None => return Witness::And(vec![]),
+                    };
+                }
+                ret
+            }
+        }
+    }
+
+    /// A value both `self` and `other` match,`None` if there is none.
+    fn unify(&self, other: &Witness) -> Option<Witness> {
+        match (self, other) {
+            (Witness::Any, x) | (x, Witness::Any) => Some(x.clone()),
+            (Witness::Int(a, _), Witness::Int(b, _)) => (a == b).then(|| self.clone()),
+            (Witness::Prim(a), Witness::Prim(b)) => (a == b).then(|| self.clone()),
+            (Witness::Term(a, xs), Witness::Term(b, ys)) if a == b && xs.len() == ys.len() => {
+                let mut args = Vec::with_capacity(xs.len());
+                for (x, y) in xs.iter().zip(ys.iter()) {
+                    args.push(x.unify(y)?);
+                }
+                Some(Witness::Term(a.clone(), args))
+            }
+            (Witness::Term(a, _), Witness::Term(b, _)) if is_other_variant(a, b) => None,
+            (Witness::And(xs), y) | (y, Witness::And(xs)) => {
+                let mut xs = xs.clone();
+                xs.push(y.clone());
+                Some(Witness::And(xs))
+            }
+            (x, y) => Some(Witness::And(vec![x.clone(), y.clone()])),
+        }
+    }
+}
+
+/// `Type.A` and `Type.B` never match the same value.
+fn is_other_variant(a: &str, b: &str) -> bool {
+    match (a.split_once('.'), b.split_once('.')) {
+        (Some((x, _)), Some((y, _))) => x == y,
+        _ => false,
+    }
+}
+
+impl std::fmt::Display for Witness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Witness::Any => write!(f, "_"),
+            Witness::Int(_, x) | Witness::Prim(x) => write!(f, "{}", x),
+            Witness::Term(name, args) => {
+                write!(f, "({}", name)?;
+                for a in args.iter() {
+                    write!(f, " {}", a)?;
+                }
+                write!(f, ")")
+            }
+            Witness::And(xs) => {
+                write!(f, "(and")?;
+                for x in xs.iter() {
+                    write!(f, " {}", x)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn find_rule<'a>(p: &'a Project, pos: &Pos) -> Option<&'a Rule> {
+    p.defs.defs.iter().find_map(|d| match d {
+        Def::Rule(r) if &r.pos == pos => Some(r),
+        _ => None,
+    })
+}
+
+/// Whether a variable is bound more than once in `pat`.
+fn has_repeated_var(pat: &Pattern) -> bool {
+    fn collect<'a>(pat: &'a Pattern, vars: &mut Vec<&'a str>) -> bool {
+        let mut bind = |name: &'a str| {
+            if vars.contains(&name) {
+                return true;
+            }
+            vars.push(name);
+            false
+        };
+        match pat {
+            Pattern::Var { var, .. } => bind(var.0.as_str()),
+            Pattern::BindPattern { var, subpat, .. } => {
+                bind(var.0.as_str()) || collect(subpat.as_ref(), vars)
+            }
+            Pattern::Term { args, .. } => args.iter().any(|x| collect(x, vars)),
+            Pattern::And { subpats, .. } => subpats.iter().any(|x| collect(x, vars)),
+            Pattern::ConstInt { .. }
+            | Pattern::ConstPrim { .. }
+            | Pattern::Wildcard { .. }
+            | Pattern::MacroArg { .. } => false,
+        }
+    }
+    collect(pat, &mut Vec::new())
+}
+
+/// A input both rules at `a` and `b` match,in `ISLE` syntax.
+pub fn overlap_witness(p: &Project, a: &Pos, b: &Pos) -> Option<String> {
+    let a = &find_rule(p, a)?.pattern;
+    let b = &find_rule(p, b)?.pattern;
+    if has_repeated_var(a) || has_repeated_var(b) {
+        return None;
+    }
+    let a = Witness::from_pattern(p, a);
+    let b = Witness::from_pattern(p, b);
+    a.unify(&b).map(|x| x.to_string())
+}
+
+#[derive(Clone, serde::Deserialize)]
+struct Req {
+    fpath: String,
+    line: u32,
+    col: u32,
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct Explanation {
+    /// The other rule.
+    pub(crate) rule: Location,
+    pub(crate) witness: String,
+}
+
+/// Handles `isle/explain_overlap`,explain overlaps of the rule at the position.
+pub fn on_explain_overlap(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<Req>(request.params.clone())
+        .expect("could not deserialize explain overlap request");
+    let fpath = PathBuf::from_str(parameters.fpath.as_str()).unwrap();
+    let pos = Position::new(parameters.line, parameters.col);
+    match explain_overlap(&context.project, &fpath, pos) {
+        Some(x) => {
+            let r = Response::new_ok(request.id.clone(), serde_json::to_value(x).unwrap());
+            context
+                .connection
+                .sender
+                .send(Message::Response(r))
+                .unwrap();
+        }
+        None => send_err(context, "No overlap found".to_string(), request.id.clone()),
+    }
+}
+
+pub(crate) fn explain_overlap(
+    p: &Project,
+    fpath: &Path,
+    pos: Position,
+) -> Option<Vec<Explanation>> {
+    let file_index = p.found_file_index(fpath)?;
+    let content = p.file_text(fpath)?;
+    let forms = crate::sexpr::parse_sexprs(content.as_str(), fpath.to_str().unwrap()).ok()?;
+    let form = forms.iter().find(|f| {
+        let r = f.range();
+        r.start <= pos && pos <= r.end
+    })?;
+    let rule = p.defs.defs.iter().find_map(|d| match d {
+        Def::Rule(r) if r.pos.file == file_index && form.contains_pos(&r.pos) => Some(r.pos),
+        _ => None,
+    })?;
+    let mut ret = Vec::new();
+    for c in conflicts(&p.defs).into_iter() {
+        if c.mask.is_some() || !c.rules.contains(&rule) {
+            continue;
+        }
+        for other in c.rules.iter().filter(|x| **x != rule) {
+            let witness = match overlap_witness(p, &rule, other) {
+                Some(x) => x,
+                None => continue,
+            };
+            let fpath = match p.file_index_path(other.file) {
+                Some(x) => x,
+                None => continue,
+            };
+            if let Some(range) = rule_range(p, other) {
+                ret.push(Explanation {
+                    rule: Location {
+                        uri: Url::from_file_path(fpath).unwrap(),
+                        range,
+                    },
+                    witness,
+                });
+            }
+        }
+    }
+    if !ret.is_empty() {
+        Some(ret)
+    } else {
+        None
+    }
+}
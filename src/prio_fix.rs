@@ -0,0 +1,216 @@
+//! Fix `OverlapError` and `ShadowedError` by changing the priority of a rule.
+//! Each candidate priority is checked by compiling the project in memory,
+//! a fix must resolve the conflict and not introduce a new one.
+
+use super::code_action::overlaps;
+use super::project::*;
+use crate::sexpr::{parse_sexprs, pos_to_position, rule_prio, RulePrio};
+use cranelift_isle::ast::*;
+use cranelift_isle::codegen::CodegenOptions;
+use cranelift_isle::error::Error;
+use cranelift_isle::lexer::Pos;
+use lsp_types::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// How many priorities are tried for a rule.
+const MAX_TRIES: i64 = 2;
+
+/// How many times the project is compiled for one request,
+/// code actions are computed on the main loop so keep it small.
+const MAX_COMPILES: usize = 4;
+
+/// `code` of the diagnostics of a `OverlapError`.
+pub const OVERLAP_CODE: &str = "overlap";
+/// `code` of the diagnostics of a `ShadowedError`.
+pub const SHADOWED_CODE: &str = "shadowed";
+
+/// Whether `d` reports a conflict `prio_fixes` may fix.
+pub(crate) fn is_conflict(d: &Diagnostic) -> bool {
+    [OVERLAP_CODE, SHADOWED_CODE]
+        .iter()
+        .any(|x| d.code == Some(NumberOrString::String(x.to_string())))
+}
+
+/// Rules of a overlap or a shadowing,the mask of a shadowing is the last one.
+pub(crate) struct Conflict {
+    pub(crate) rules: Vec<Pos>,
+    /// `Some` for a shadowing.
+    pub(crate) mask: Option<Pos>,
+}
+
+impl Conflict {
+    fn same(&self, other: &Conflict) -> bool {
+        self.mask == other.mask
+            && self.rules.len() == other.rules.len()
+            && self.rules.iter().all(|x| other.rules.contains(x))
+    }
+}
+
+/// Overlaps and shadowings `defs` has.
+pub(crate) fn conflicts(defs: &Defs) -> Vec<Conflict> {
+    let options = CodegenOptions {
+        exclude_global_allow_pragmas: false,
+    };
+    match cranelift_isle::compile::compile(&without_false_defs(defs), &options) {
+        Ok(_) => vec![],
+        Err(err) => err
+            .errors
+            .into_iter()
+            .filter_map(|e| match e {
+                Error::OverlapError { rules, .. } => Some(Conflict {
+                    rules: rules.iter().map(|x| x.from).collect(),
+                    mask: None,
+                }),
+                Error::ShadowedError { shadowed, mask } => Some(Conflict {
+                    rules: shadowed
+                        .iter()
+                        .chain(std::iter::once(&mask))
+                        .map(|x| x.from)
+                        .collect(),
+                    mask: Some(mask.from),
+                }),
+                _ => None,
+            })
+            .collect(),
+    }
+}
+
+fn rule_index(defs: &Defs, pos: &Pos) -> Option<usize> {
+    defs.defs.iter().position(|d| match d {
+        Def::Rule(r) => &r.pos == pos,
+        _ => false,
+    })
+}
+
+fn rule_prio_value(defs: &Defs, index: usize) -> i64 {
+    match &defs.defs[index] {
+        Def::Rule(r) => r.prio.unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Priority changes fix conflicts of rules in `range` of `fpath`.
+pub(crate) fn prio_fixes(
+    p: &Project,
+    fpath: &Path,
+    range: &Range,
+    actions: &mut Vec<CodeActionOrCommand>,
+) {
+    let file_index = match p.found_file_index(fpath) {
+        Some(x) => x,
+        None => return,
+    };
+    let in_range = |pos: &Pos| {
+        pos.file == file_index && rule_range(p, pos).map(|r| overlaps(&r, range)) == Some(true)
+    };
+    let baseline = conflicts(&p.defs);
+    let mut compiles = 1;
+    // rule and priority already proposed.
+    let mut proposed: Vec<(Pos, i64)> = Vec::new();
+    for c in baseline.iter() {
+        if !c.rules.iter().any(&in_range) {
+            continue;
+        }
+        for rule in c.rules.iter() {
+            if Some(*rule) == c.mask {
+                // raising the mask makes it worse.
+                continue;
+            }
+            let index = match rule_index(&p.defs, rule) {
+                Some(x) => x,
+                None => continue,
+            };
+            let start = match c.mask {
+                Some(mask) => match rule_index(&p.defs, &mask) {
+                    Some(x) => rule_prio_value(&p.defs, x) + 1,
+                    None => continue,
+                },
+                None => rule_prio_value(&p.defs, index) + 1,
+            };
+            for prio in start..start + MAX_TRIES {
+                if compiles >= MAX_COMPILES {
+                    return;
+                }
+                compiles += 1;
+                let mut defs = p.defs.clone();
+                if let Def::Rule(r) = &mut defs.defs[index] {
+                    r.prio = Some(prio);
+                }
+                let after = conflicts(&defs);
+                let resolved = !after.iter().any(|x| x.same(c));
+                let no_new = after.iter().all(|x| baseline.iter().any(|b| b.same(x)));
+                if resolved && no_new {
+                    if !proposed.contains(&(*rule, prio)) {
+                        proposed.push((*rule, prio));
+                        if let Some(action) = prio_action(p, rule, prio) {
+                            actions.push(action);
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Set the priority of the rule at `pos` to `prio`,previewed before applied.
+fn prio_action(p: &Project, pos: &Pos, prio: i64) -> Option<CodeActionOrCommand> {
+    let fpath = p.file_index_path(pos.file)?;
+    let content = p.file_text(&fpath)?;
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).ok()?;
+    let form = forms.iter().find(|x| x.contains_pos(pos))?;
+    let (range, new_text) = match rule_prio(form)? {
+        RulePrio::Explicit(x) => (
+            Range {
+                start: pos_to_position(&x.start),
+                end: pos_to_position(&x.end),
+            },
+            prio.to_string(),
+        ),
+        RulePrio::After(x) => {
+            let end = pos_to_position(&x.end);
+            (Range { start: end, end }, format!(" {}", prio))
+        }
+    };
+    let annotation = "prio".to_string();
+    let mut annotations = HashMap::new();
+    annotations.insert(
+        annotation.clone(),
+        ChangeAnnotation {
+            label: format!("Set priority to {}", prio),
+            needs_confirmation: Some(true),
+            description: None,
+        },
+    );
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Set priority of rule at line {} to {}", pos.line, prio),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri: Url::from_file_path(&fpath).ok()?,
+                    version: None,
+                },
+                edits: vec![OneOf::Right(AnnotatedTextEdit {
+                    text_edit: TextEdit { range, new_text },
+                    annotation_id: annotation,
+                })],
+            }])),
+            change_annotations: Some(annotations),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Range of the whole rule at `pos`.
+pub(crate) fn rule_range(p: &Project, pos: &Pos) -> Option<Range> {
+    let fpath = p.file_index_path(pos.file)?;
+    let content = p.file_text(&fpath)?;
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).ok()?;
+    forms
+        .iter()
+        .find(|x| x.contains_pos(pos))
+        .map(|x| x.range())
+}
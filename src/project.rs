@@ -1,13 +1,15 @@
 use super::item::*;
 use crate::comment::{CommentExtrator, DocumentComments};
 use crate::item;
+use crate::sema_info::SemaInfo;
+use crate::sexpr::local_offset;
 use crate::utils::GetPosAndLength;
 use cranelift_isle::{ast::*, error::Errors, lexer::*, parser::*};
 use lsp_types::Position;
 use lsp_types::Range;
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -17,6 +19,8 @@ pub struct Project {
 
     pub(crate) context: VisitContext,
     pub(crate) comments: HashMap<PathBuf, DocumentComments>,
+    /// Built on first use after `defs` changed,see `Project::sema`.
+    sema: OnceCell<Option<SemaInfo>>,
 }
 
 impl Project {
@@ -30,8 +34,13 @@ impl Project {
 
             context: Default::default(),
             comments: Default::default(),
+            sema: OnceCell::new(),
         }
     }
+    /// Names and types resolved by `sema`,`None` if the project does not type check.
+    pub(crate) fn sema(&self) -> Option<&SemaInfo> {
+        self.sema.get_or_init(|| SemaInfo::new(&self.defs)).as_ref()
+    }
     pub fn get_filenames(&self) -> &Vec<Arc<str>> {
         &self.defs.filenames
     }
@@ -89,6 +98,7 @@ impl Project {
             defs,
             context: VisitContext::new(),
             comments,
+            sema: OnceCell::new(),
         };
 
         let mut comments = HashMap::new();
@@ -113,7 +123,17 @@ impl Project {
             .unwrap_or("")
     }
 
-    pub fn run_visitor_for_file(&self, p: &PathBuf, handler: &mut dyn ItemOrAccessHandler) {
+    /// Source text of the atom (symbol or integer literal) starts at `pos`.
+    pub(crate) fn atom_text(&self, pos: &Pos) -> Option<&str> {
+        let content = self.file_content(pos.file);
+        let rest = content.get(local_offset(content, pos)..)?;
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '(' || c == ')' || c == ';')
+            .unwrap_or(rest.len());
+        Some(&rest[..end])
+    }
+
+    pub fn run_visitor_for_file(&self, p: &Path, handler: &mut dyn ItemOrAccessHandler) {
         let provider = match self.found_file_defs(p) {
             Some(x) => x,
             None => {
@@ -123,7 +143,7 @@ impl Project {
         };
         self.visit(provider, handler);
     }
-    pub(crate) fn found_file_defs<'a>(&'a self, p: &PathBuf) -> Option<VecDefAstProvider<'a>> {
+    pub(crate) fn found_file_defs<'a>(&'a self, p: &Path) -> Option<VecDefAstProvider<'a>> {
         let file_index = match self.found_file_index(p) {
             Some(x) => x,
             None => {
@@ -150,7 +170,7 @@ impl Project {
         VecDefAstProvider::new(ret)
     }
 
-    fn found_file_index(&self, p: &PathBuf) -> Option<usize> {
+    pub(crate) fn found_file_index(&self, p: &Path) -> Option<usize> {
         for (index, x) in self.defs.filenames.iter().enumerate() {
             if p.to_str().unwrap() == x.as_ref() {
                 return Some(index);
@@ -159,6 +179,14 @@ impl Project {
         None
     }
 
+    /// Content of file `p`,read from disk if `p` is not part of the project.
+    pub(crate) fn file_text(&self, p: &Path) -> Option<String> {
+        match self.found_file_index(p) {
+            Some(index) => Some(self.file_content(index).to_string()),
+            None => std::fs::read_to_string(p).ok(),
+        }
+    }
+
     pub(crate) fn file_index_path(&self, index: usize) -> Option<PathBuf> {
         self.defs
             .filenames
@@ -237,6 +265,8 @@ impl Project {
         }
 
         self.context.delete_old_defs(file_index);
+        self.defs.file_texts[file_index] = Arc::from(content);
+        self.sema = OnceCell::new();
 
         let mut dummy = DummyHandler {};
         self.run_visitor_for_file(p, &mut dummy);
@@ -380,7 +410,7 @@ impl VisitContext {
 
     pub(crate) fn query_item<R>(
         &self,
-        name: &String,
+        name: &str,
         mut call_back: impl FnMut(&Item) -> R,
     ) -> Option<R> {
         for s in self.scopes.as_ref().borrow().iter().rev() {
@@ -391,13 +421,13 @@ impl VisitContext {
         }
         None
     }
-    pub(crate) fn query_item_clone(&self, name: &String) -> Item {
+    pub(crate) fn query_item_clone(&self, name: &str) -> Item {
         self.query_item(name, |x| x.clone()).unwrap_or_default()
     }
 
     pub(crate) fn query_const<R>(
         &self,
-        name: &String,
+        name: &str,
         mut call_back: impl FnMut(&Item) -> R,
     ) -> Option<R> {
         if let Some(x) = self
@@ -419,7 +449,7 @@ impl VisitContext {
 
         None
     }
-    pub(crate) fn query_const_clone(&self, name: &String) -> Item {
+    pub(crate) fn query_const_clone(&self, name: &str) -> Item {
         self.query_const(name, |x| x.clone()).unwrap_or_default()
     }
 
@@ -470,6 +500,24 @@ lazy_static! {
     });
 }
 
+/// `defs` without the `FALSE_DEF` placeholders left by `update_defs`,
+/// they all have the same empty name and would be rejected by the compiler.
+pub(crate) fn without_false_defs(defs: &Defs) -> Defs {
+    Defs {
+        defs: defs
+            .defs
+            .iter()
+            .filter(|d| match d {
+                Def::Type(x) => !x.name.0.is_empty(),
+                _ => true,
+            })
+            .cloned()
+            .collect(),
+        filenames: defs.filenames.clone(),
+        file_texts: defs.file_texts.clone(),
+    }
+}
+
 pub trait AstProvider: Clone {
     fn with_def(&self, call_back: impl FnMut(&Def));
     fn with_pragma(&self, mut call_back: impl FnMut(&Pragma)) {
@@ -517,12 +565,12 @@ pub trait AstProvider: Clone {
 }
 
 #[derive(Clone)]
-struct ProjectAstProvider<'a> {
+pub(crate) struct ProjectAstProvider<'a> {
     p: &'a Project,
 }
 
 impl<'a> ProjectAstProvider<'a> {
-    fn new(p: &'a Project) -> Self {
+    pub(crate) fn new(p: &'a Project) -> Self {
         Self { p }
     }
 }
@@ -1,6 +1,7 @@
 use super::item::*;
 use super::project::*;
 use cranelift_isle::ast::*;
+use cranelift_isle::lexer::Pos;
 
 impl Project {
     pub(crate) fn visit(&self, provider: impl AstProvider, handler: &mut dyn ItemOrAccessHandler) {
@@ -98,19 +99,25 @@ impl Project {
         });
 
         // fix decl type
+        // rules and externs of a term may in other files.
         {
+            let provider = ProjectAstProvider::new(self);
             provider.with_extern(|x| match x {
                 Extern::Extractor {
                     term,
                     func: _,
                     pos: _,
                     infallible: _infallible,
-                } => self.context.fix_decl_type(&term.0, DeclKind::EXTRATOR),
+                } => self
+                    .context
+                    .fix_decl_type(&term.0, DeclKind::EXTRATOR | DeclKind::EXTERN),
                 Extern::Constructor {
                     term,
                     func: _,
                     pos: _,
-                } => self.context.fix_decl_type(&term.0, DeclKind::CONSTRUCTOR),
+                } => self
+                    .context
+                    .fix_decl_type(&term.0, DeclKind::CONSTRUCTOR | DeclKind::EXTERN),
                 Extern::Const {
                     name: _,
                     ty: _,
@@ -136,16 +143,9 @@ impl Project {
         // handle extern
         provider.with_extern(|x| match x {
             Extern::Extractor {
-                term,
-                func: _,
-                pos: _,
-                ..
+                term, func, pos: _, ..
             }
-            | Extern::Constructor {
-                term,
-                func: _,
-                pos: _,
-            } => {
+            | Extern::Constructor { term, func, pos: _ } => {
                 let item = ItemOrAccess::Access(Access {
                     kind: AccessKind::DeclExtern,
                     access: term.clone(),
@@ -155,6 +155,8 @@ impl Project {
                         .unwrap_or_default(),
                 });
                 handler.handle_item_or_access(self, &item);
+                let item = ItemOrAccess::Item(Item::ExternFunc { name: func.clone() });
+                handler.handle_item_or_access(self, &item);
             }
             Extern::Const { .. } => {}
         });
@@ -221,7 +223,13 @@ impl Project {
                         return;
                     }
                 }
-                self.apply_expr(&d.expr, handler);
+                let ret_ty = get_rule_target(&d.pattern)
+                    .map(|(name, _)| match self.context.query_item_clone(name) {
+                        Item::Decl { decl, .. } => Some(decl.ret_ty),
+                        _ => None,
+                    })
+                    .flatten();
+                self.apply_expr_with_ty(&d.expr, ret_ty.as_ref(), handler);
             };
             self.context.enter_scope(call);
         });
@@ -238,6 +246,25 @@ impl Project {
         });
         handler.handle_item_or_access(self, &item);
     }
+
+    fn visit_const_int(
+        &self,
+        val: i128,
+        pos: Pos,
+        ty: Option<&Ident>,
+        handler: &mut dyn ItemOrAccessHandler,
+    ) {
+        let lit = self
+            .atom_text(&pos)
+            .map(|x| x.to_string())
+            .unwrap_or(val.to_string());
+        let item = ItemOrAccess::Item(Item::ConstInt {
+            lit: Ident(lit, pos),
+            val,
+            ty: ty.cloned().unwrap_or(UNKNOWN_TYPE.clone()),
+        });
+        handler.handle_item_or_access(self, &item);
+    }
 }
 
 fn find_variant<'a>(xs: &'a Vec<Variant>, name: &str) -> Option<&'a Variant> {
@@ -251,6 +278,16 @@ fn find_variant<'a>(xs: &'a Vec<Variant>, name: &str) -> Option<&'a Variant> {
 
 impl Project {
     pub(crate) fn apply_matcher(&self, p: &Pattern, handler: &mut dyn ItemOrAccessHandler) {
+        self.apply_matcher_with_ty(p, None, handler)
+    }
+
+    /// `ty` is the type expected by the enclosing term if known.
+    pub(crate) fn apply_matcher_with_ty(
+        &self,
+        p: &Pattern,
+        ty: Option<&Ident>,
+        handler: &mut dyn ItemOrAccessHandler,
+    ) {
         let handle_term = |sym: &Ident,
                            handler: &mut dyn ItemOrAccessHandler|
          -> Option<(
@@ -282,7 +319,8 @@ impl Project {
                         .unwrap_or_default();
                     let item = ItemOrAccess::Access(Access {
                         kind: AccessKind::ApplyEORC,
-                        access: sym.clone(),
+                        // only the `Type` half of `Type.Variant`.
+                        access: x.clone().into(),
                         def: def.clone(),
                     });
                     handler.handle_item_or_access(self, &item);
@@ -307,12 +345,10 @@ impl Project {
                                     def: Item::EnumVariant { v: v.clone() },
                                 });
                                 handler.handle_item_or_access(self, &item);
-                                if handler.finished() {
-                                    return Some((
-                                        v.fields.iter().map(|x| x.ty.clone()).collect(),
-                                        ty.name.clone(),
-                                    ));
-                                }
+                                return Some((
+                                    v.fields.iter().map(|x| x.ty.clone()).collect(),
+                                    ty.name.clone(),
+                                ));
                             }
                         },
                         _ => {}
@@ -325,6 +361,17 @@ impl Project {
 
         match p {
             Pattern::Var { var, .. } => {
+                // bound here by a `if-let`,a name bound before is a equality check.
+                if let Some(ty) = self.sema().and_then(|x| x.var_type(var)) {
+                    let item = ItemOrAccess::Item(Item::Var {
+                        name: var.clone(),
+                        ty: ty.clone(),
+                        has_decl_type: false,
+                    });
+                    handler.handle_item_or_access(self, &item);
+                    self.context.enter_item(var.0.clone(), item);
+                    return;
+                }
                 // in top level
                 let item = ItemOrAccess::Access(Access {
                     access: var.clone(),
@@ -334,10 +381,10 @@ impl Project {
                 handler.handle_item_or_access(self, &item);
             }
             Pattern::BindPattern { subpat, .. } => {
-                self.apply_matcher(subpat.as_ref(), handler);
+                self.apply_matcher_with_ty(subpat.as_ref(), ty, handler);
             }
 
-            Pattern::ConstInt { .. } => {}
+            Pattern::ConstInt { val, pos } => self.visit_const_int(*val, *pos, ty, handler),
             Pattern::ConstPrim { val, .. } => {
                 let item = ItemOrAccess::Access(Access {
                     access: val.clone(),
@@ -351,13 +398,17 @@ impl Project {
                 // first pass.
                 let enter_var =
                     |index: usize, var: &Ident, handler: &mut dyn ItemOrAccessHandler| {
-                        let ty = tys
-                            .as_ref()
-                            .map(|x| &x.0)
-                            .map(|x| x.get(index))
-                            .flatten()
-                            .map(|x| x.clone())
-                            .unwrap_or(crate::item::UNKNOWN_TYPE.clone());
+                        // sema knows better than the decl when it type checks.
+                        let ty = match self.sema().and_then(|x| x.var_type(var)) {
+                            Some(x) => x.clone(),
+                            None => tys
+                                .as_ref()
+                                .map(|x| &x.0)
+                                .map(|x| x.get(index))
+                                .flatten()
+                                .map(|x| x.clone())
+                                .unwrap_or(crate::item::UNKNOWN_TYPE.clone()),
+                        };
                         let item = ItemOrAccess::Item(Item::Var {
                             name: var.clone(),
                             ty,
@@ -375,13 +426,17 @@ impl Project {
                         Pattern::BindPattern { var, .. } => {
                             enter_var(index, var, handler);
                         }
-                        Pattern::ConstInt { .. } => {}
+                        Pattern::ConstInt { val, pos } => {
+                            let ty = tys.as_ref().map(|x| x.0.get(index)).flatten();
+                            self.visit_const_int(*val, *pos, ty, handler);
+                        }
                         Pattern::ConstPrim { .. } => self.apply_matcher(a, handler),
                         Pattern::Term { .. } => self.apply_matcher(a, handler),
                         Pattern::Wildcard { .. } => {}
                         Pattern::And { subpats, .. } => {
+                            let ty = tys.as_ref().and_then(|x| x.0.get(index));
                             for s in subpats.iter() {
-                                self.apply_matcher(s, handler);
+                                self.apply_matcher_with_ty(s, ty, handler);
                             }
                         }
                         Pattern::MacroArg { .. } => {}
@@ -391,7 +446,7 @@ impl Project {
             Pattern::Wildcard { .. } => {}
             Pattern::And { subpats, .. } => {
                 for s in subpats.iter() {
-                    self.apply_matcher(s, handler);
+                    self.apply_matcher_with_ty(s, ty, handler);
                 }
             }
             Pattern::MacroArg { .. } => {}
@@ -401,69 +456,83 @@ impl Project {
 
 impl Project {
     pub(crate) fn apply_expr(&self, e: &Expr, handler: &mut dyn ItemOrAccessHandler) {
+        self.apply_expr_with_ty(e, None, handler)
+    }
+
+    /// `ty` is the type expected by the enclosing term if known.
+    pub(crate) fn apply_expr_with_ty(
+        &self,
+        e: &Expr,
+        ty: Option<&Ident>,
+        handler: &mut dyn ItemOrAccessHandler,
+    ) {
         let handle_term =
-            |sym: &Ident, handler: &mut dyn ItemOrAccessHandler| match SplitedSymbol::from(sym) {
-                SplitedSymbol::One(_) => {
-                    let item = ItemOrAccess::Access(Access {
-                        kind: AccessKind::ApplyEORC,
-                        access: sym.clone(),
-                        def: self
+            |sym: &Ident, handler: &mut dyn ItemOrAccessHandler| -> Option<Vec<Ident>> {
+                match SplitedSymbol::from(sym) {
+                    SplitedSymbol::One(_) => {
+                        let def = self
                             .context
                             .query_item(&sym.0, |x| x.clone())
-                            .unwrap_or_default(),
-                    });
-                    handler.handle_item_or_access(self, &item);
-                    if handler.finished() {
-                        return;
-                    }
-                }
-                SplitedSymbol::Two([x, y]) => {
-                    let def = self
-                        .context
-                        .query_item(&x.symbol, |x| x.clone())
-                        .unwrap_or_default();
-                    let item = ItemOrAccess::Access(Access {
-                        kind: AccessKind::ApplyEORC,
-
-                        access: sym.clone(),
-                        def: def.clone(),
-                    });
-                    handler.handle_item_or_access(self, &item);
-                    if handler.finished() {
-                        return;
+                            .unwrap_or_default();
+                        let item = ItemOrAccess::Access(Access {
+                            kind: AccessKind::ApplyEORC,
+                            access: sym.clone(),
+                            def: def.clone(),
+                        });
+                        handler.handle_item_or_access(self, &item);
+                        match def {
+                            Item::Decl { decl, .. } => Some(decl.arg_tys),
+                            _ => None,
+                        }
                     }
-                    match def {
-                        Item::Type { ty } => match &ty.ty {
-                            TypeValue::Primitive(_, _) => {}
-                            TypeValue::Enum(variants, _) => {
-                                let v = find_variant(variants, y.symbol.as_str())
-                                    .map(|x| x.clone())
-                                    .unwrap_or(Variant {
-                                        name: Ident(y.symbol.clone(), y.pos),
-                                        fields: vec![],
-                                        pos: y.pos,
+                    SplitedSymbol::Two([x, y]) => {
+                        let def = self
+                            .context
+                            .query_item(&x.symbol, |x| x.clone())
+                            .unwrap_or_default();
+                        let item = ItemOrAccess::Access(Access {
+                            kind: AccessKind::ApplyEORC,
+                            // only the `Type` half of `Type.Variant`.
+                            access: x.clone().into(),
+                            def: def.clone(),
+                        });
+                        handler.handle_item_or_access(self, &item);
+                        if handler.finished() {
+                            return None;
+                        }
+                        match def {
+                            Item::Type { ty } => match &ty.ty {
+                                TypeValue::Primitive(_, _) => None,
+                                TypeValue::Enum(variants, _) => {
+                                    let v = find_variant(variants, y.symbol.as_str())
+                                        .map(|x| x.clone())
+                                        .unwrap_or(Variant {
+                                            name: Ident(y.symbol.clone(), y.pos),
+                                            fields: vec![],
+                                            pos: y.pos,
+                                        });
+                                    let tys = v.fields.iter().map(|x| x.ty.clone()).collect();
+                                    let item = ItemOrAccess::Access(Access {
+                                        access: y.clone().into(),
+                                        kind: AccessKind::ApplyVariant(x.symbol.clone()),
+                                        def: Item::EnumVariant { v },
                                     });
-                                let item = ItemOrAccess::Access(Access {
-                                    access: y.clone().into(),
-                                    kind: AccessKind::ApplyVariant(x.symbol.clone()),
-                                    def: Item::EnumVariant { v },
-                                });
-                                handler.handle_item_or_access(self, &item);
-                                if handler.finished() {
-                                    return;
+                                    handler.handle_item_or_access(self, &item);
+                                    Some(tys)
                                 }
-                            }
-                        },
-                        _ => {}
-                    };
+                            },
+                            _ => None,
+                        }
+                    }
                 }
             };
 
         match e {
             Expr::Term { sym, args, .. } => {
-                handle_term(sym, handler);
-                for e in args.iter() {
-                    self.apply_expr(e, handler);
+                let tys = handle_term(sym, handler);
+                for (index, e) in args.iter().enumerate() {
+                    let ty = tys.as_ref().map(|x| x.get(index)).flatten();
+                    self.apply_expr_with_ty(e, ty, handler);
                 }
             }
             Expr::Var { name, .. } => {
@@ -474,7 +543,7 @@ impl Project {
                 });
                 handler.handle_item_or_access(self, &item);
             }
-            Expr::ConstInt { .. } => {}
+            Expr::ConstInt { val, pos } => self.visit_const_int(*val, *pos, ty, handler),
             Expr::ConstPrim { val, .. } => {
                 let item = ItemOrAccess::Access(Access {
                     access: val.clone(),
@@ -487,7 +556,7 @@ impl Project {
             Expr::Let { defs, body, .. } => {
                 let call = || {
                     for d in defs.iter() {
-                        self.apply_expr(&d.val, handler);
+                        self.apply_expr_with_ty(&d.val, Some(&d.ty), handler);
                         if handler.finished() {
                             return;
                         }
@@ -506,7 +575,7 @@ impl Project {
                         }
                         self.context.enter_item(d.var.0.clone(), item);
                     }
-                    self.apply_expr(body.as_ref(), handler);
+                    self.apply_expr_with_ty(body.as_ref(), ty, handler);
                 };
                 self.context.enter_scope(call);
             }
@@ -516,6 +585,16 @@ impl Project {
 
 impl Project {
     pub(crate) fn apply_extractor(&self, p: &Pattern, handler: &mut dyn ItemOrAccessHandler) {
+        self.apply_extractor_with_ty(p, None, handler)
+    }
+
+    /// `ty` is the type expected by the enclosing term if known.
+    fn apply_extractor_with_ty(
+        &self,
+        p: &Pattern,
+        ty: Option<&Ident>,
+        handler: &mut dyn ItemOrAccessHandler,
+    ) {
         match p {
             Pattern::Var { var, pos: _ } => {
                 let item = ItemOrAccess::Access(Access {
@@ -544,9 +623,9 @@ impl Project {
                 if handler.finished() {
                     return;
                 }
-                self.apply_extractor(subpat.as_ref(), handler);
+                self.apply_extractor_with_ty(subpat.as_ref(), ty, handler);
             }
-            Pattern::ConstInt { .. } => {}
+            Pattern::ConstInt { val, pos } => self.visit_const_int(*val, *pos, ty, handler),
             Pattern::ConstPrim { val, .. } => {
                 let item = ItemOrAccess::Access(Access {
                     access: val.clone(),
@@ -562,15 +641,21 @@ impl Project {
                 }
             }
             Pattern::Term { sym, args, pos: _ } => {
+                // types of the arguments.
+                let mut tys = Vec::new();
                 match SplitedSymbol::from(sym) {
                     SplitedSymbol::One(_) => {
+                        let def = self
+                            .context
+                            .query_item(&sym.0, |x| x.clone())
+                            .unwrap_or_default();
+                        if let Item::Decl { decl, .. } = &def {
+                            tys = decl.arg_tys.clone();
+                        }
                         let item = ItemOrAccess::Access(Access {
                             access: sym.clone(),
                             kind: AccessKind::ApplyEORC,
-                            def: self
-                                .context
-                                .query_item(&sym.0, |x| x.clone())
-                                .unwrap_or_default(),
+                            def,
                         });
                         handler.handle_item_or_access(self, &item);
                         if handler.finished() {
@@ -584,7 +669,8 @@ impl Project {
                             .unwrap_or_default();
                         let item = ItemOrAccess::Access(Access {
                             kind: AccessKind::ApplyEORC,
-                            access: sym.clone(),
+                            // only the `Type` half of `Type.Variant`.
+                            access: x.clone().into(),
                             def: def.clone(),
                         });
                         handler.handle_item_or_access(self, &item);
@@ -602,6 +688,7 @@ impl Project {
                                             fields: vec![],
                                             pos: y.pos,
                                         });
+                                    tys = v.fields.iter().map(|x| x.ty.clone()).collect();
                                     let item = ItemOrAccess::Access(Access {
                                         kind: AccessKind::ApplyVariant(x.symbol.clone()),
                                         access: y.clone().into(),
@@ -617,8 +704,8 @@ impl Project {
                         };
                     }
                 }
-                for a in args.iter() {
-                    self.apply_extractor(a, handler);
+                for (index, a) in args.iter().enumerate() {
+                    self.apply_extractor_with_ty(a, tys.get(index), handler);
                     if handler.finished() {
                         return;
                     }
@@ -627,7 +714,7 @@ impl Project {
             Pattern::Wildcard { pos: _ } => {}
             Pattern::And { subpats, pos: _ } => {
                 for s in subpats.iter() {
-                    self.apply_extractor(s, handler);
+                    self.apply_extractor_with_ty(s, ty, handler);
                     if handler.finished() {
                         return;
                     }
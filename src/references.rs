@@ -6,7 +6,7 @@ use super::project::*;
 use cranelift_isle::lexer::Pos;
 use lsp_server::*;
 use lsp_types::*;
-use std::collections::HashSet;
+use std::collections::HashMap;
 
 pub fn on_references_request(context: &mut Context, request: &Request) {
     let parameters = serde_json::from_value::<ReferenceParams>(request.params.clone())
@@ -52,7 +52,7 @@ pub fn on_references_request(context: &mut Context, request: &Request) {
 pub(crate) struct Handler {
     def_loc: (Pos, u32),
     include_declaration: bool,
-    refs: HashSet<(Pos, u32)>,
+    refs: HashMap<(Pos, u32), AccessKind>,
 }
 
 impl Handler {
@@ -72,7 +72,7 @@ impl Handler {
                 file_ranges.push(l);
             }
         }
-        for x in self.refs.iter() {
+        for x in self.refs.keys() {
             let l = p.mk_location(x);
             if let Some(l) = l {
                 file_ranges.push(l);
@@ -80,6 +80,45 @@ impl Handler {
         }
         file_ranges
     }
+
+    /// Highlights in file `fpath`, `def` is the item we are looking for.
+    pub(crate) fn to_document_highlights(
+        self,
+        p: &Project,
+        fpath: &Url,
+        def: &Item,
+    ) -> Vec<DocumentHighlight> {
+        let mut ret = Vec::with_capacity(self.refs.len() + 1);
+        if self.include_declaration {
+            if let Some(l) = p.mk_location(&self.def_loc) {
+                if &l.uri == fpath {
+                    ret.push(DocumentHighlight {
+                        range: l.range,
+                        kind: Some(match def {
+                            Item::Var { .. } => DocumentHighlightKind::WRITE,
+                            _ => DocumentHighlightKind::TEXT,
+                        }),
+                    });
+                }
+            }
+        }
+        for (x, kind) in self.refs.iter() {
+            if let Some(l) = p.mk_location(x) {
+                if &l.uri == fpath {
+                    ret.push(DocumentHighlight {
+                        range: l.range,
+                        kind: Some(match kind {
+                            AccessKind::ApplyVar | AccessKind::ExtractVar => {
+                                DocumentHighlightKind::READ
+                            }
+                            _ => DocumentHighlightKind::TEXT,
+                        }),
+                    });
+                }
+            }
+        }
+        ret
+    }
 }
 
 impl ItemOrAccessHandler for Handler {
@@ -92,9 +131,9 @@ impl ItemOrAccessHandler for Handler {
             ItemOrAccess::Item(_) => {}
             ItemOrAccess::Access(access) => match item {
                 _ => {
-                    let (access, def, length) = access.access_def_loc();
+                    let (access_pos, def, length) = access.access_def_loc();
                     if def == self.def_loc.0 {
-                        self.refs.insert((access, length));
+                        self.refs.insert((access_pos, length), access.kind.clone());
                         return;
                     }
                 }
@@ -2,11 +2,19 @@ use std::collections::HashMap;
 
 use super::context::*;
 
+use crate::config::ServerConfig;
 use crate::item::ItemOrAccess;
+use crate::project::{ItemOrAccessHandler, Project};
+use crate::readable_location;
+use crate::rust_scan::{find_fn_refs, parent_dirs, rust_files, FnRefKind};
+use crate::sexpr::parse_sexprs;
+use std::path::PathBuf;
 
 use super::item::*;
 
 use super::send_err;
+use cranelift_isle::ast::{Ident, TypeValue, Variant};
+use cranelift_isle::lexer::Pos;
 use lsp_server::*;
 use lsp_types::*;
 
@@ -19,48 +27,54 @@ pub fn on_rename(context: &Context, request: &Request) {
     let line = loc.line;
     let col = loc.character;
     log::info!(
-        "request is goto definition,fpath:{:?}  line:{} col:{}",
+        "request is rename,fpath:{:?}  line:{} col:{}",
         fpath,
         line,
         col,
     );
-
-    let mut goto_definition = super::goto_definition::Handler::new(fpath.clone(), line, col);
+    let edit = match rename_workspace_edit(
+        &context.project,
+        &context.config,
+        &fpath,
+        line,
+        col,
+        parameters.new_name.as_str(),
+    ) {
+        Ok(x) => x,
+        Err(err) => {
+            send_err(context, err, request.id.clone());
+            return;
+        }
+    };
+    let r = Response::new_ok(
+        request.id.clone(),
+        serde_json::to_value(Some(edit)).unwrap(),
+    );
     context
-        .project
-        .run_visitor_for_file(&fpath.to_file_path().unwrap(), &mut goto_definition);
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
 
-    let def_loc = match goto_definition.result_item_or_access {
-        Some(x) => match x {
-            ItemOrAccess::Item(d) => d.def_loc(),
-            ItemOrAccess::Access(Access { def, .. }) => def.def_loc(),
-        },
-        None => {
-            send_err(
-                context,
-                "rename can't find def loc".to_string(),
-                request.id.clone(),
-            );
+/// Hanlde prepare rename reqeust for LSP server
+pub fn on_prepare_rename(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<TextDocumentPositionParams>(request.params.clone())
+        .expect("could not deserialize prepare rename request");
+    let fpath = parameters.text_document.uri;
+    let loc = parameters.position;
+    let (item, range) = match renamable(&context.project, &fpath, loc.line, loc.character) {
+        Ok(x) => x,
+        Err(err) => {
+            send_err(context, err, request.id.clone());
             return;
         }
     };
-    let mut refs = super::references::Handler::new(def_loc, true);
-    context.project.run_full_visitor(&mut refs);
-    let mut r = Results::default();
-    for v in refs.to_locations(&context.project).into_iter() {
-        let e = TextEdit {
-            range: v.range,
-            new_text: parameters.new_name.clone(),
-        };
-        r.insert_edit(v.uri.clone(), e);
-    }
-
     let r = Response::new_ok(
         request.id.clone(),
-        serde_json::to_value(Some(WorkspaceEdit {
-            changes: Some(r.edits),
-            document_changes: None,
-            change_annotations: None,
+        serde_json::to_value(Some(PrepareRenameResponse::RangeWithPlaceholder {
+            range,
+            placeholder: item_name(&item),
         }))
         .unwrap(),
     );
@@ -71,6 +85,370 @@ pub fn on_rename(context: &Context, request: &Request) {
         .unwrap();
 }
 
+/// Find the item at the position and the range of the symbol under cursor.
+fn renamable(p: &Project, fpath: &Url, line: u32, col: u32) -> Result<(Item, Range), String> {
+    let mut goto_definition = super::goto_definition::Handler::new(fpath.clone(), line, col);
+    p.run_visitor_for_file(&fpath.to_file_path().unwrap(), &mut goto_definition);
+    let (item, pos) = match goto_definition.result_item_or_access {
+        Some(ItemOrAccess::Item(item)) => {
+            let pos = item.def_loc().0;
+            (item, pos)
+        }
+        Some(ItemOrAccess::Access(Access { def, access, .. })) => (def, access.1),
+        None => return Err("nothing to rename at this position".to_string()),
+    };
+    match &item {
+        Item::Dummy => return Err("can't rename a unresolved name".to_string()),
+        Item::ConstInt { .. } => return Err("can't rename a literal".to_string()),
+        _ => {}
+    }
+    let range = p
+        .mk_location(&(pos, item.def_loc().1))
+        .map(|x| x.range)
+        .ok_or("location not found".to_string())?;
+    Ok((item, range))
+}
+
+/// Name of the item as written in source.
+fn item_name(item: &Item) -> String {
+    match item {
+        Item::Type { ty } => ty.name.0.clone(),
+        Item::Decl { decl, .. } => decl.term.0.clone(),
+        Item::Dummy => "".to_string(),
+        Item::Const { name, .. } => format!("${}", name.0),
+        Item::Var { name, .. } => name.0.clone(),
+        Item::EnumMemberName { name } => name.0.clone(),
+        Item::EnumMemberField { name } => name.0.clone(),
+        Item::EnumVariant { v } => v.name.0.clone(),
+        Item::ConstInt { lit, .. } => lit.0.clone(),
+        Item::ExternFunc { name } => name.0.clone(),
+    }
+}
+
+/// Compute all edits to rename the item at the position to `new_name`.
+pub(crate) fn rename_edits(
+    p: &Project,
+    fpath: &Url,
+    line: u32,
+    col: u32,
+    new_name: &str,
+) -> Result<HashMap<Url, Vec<TextEdit>>, String> {
+    let (item, _) = renamable(p, fpath, line, col)?;
+    check_new_name(p, &item, new_name)?;
+    let locations: Vec<Location> = match &item {
+        // not a `ISLE` symbol,every `extern` bind to it need change.
+        Item::ExternFunc { name } => extern_funcs(p, name.0.as_str())
+            .iter()
+            .filter_map(|x| p.mk_location(&(x.1, x.0.len() as u32)))
+            .collect(),
+        _ => {
+            let mut refs = super::references::Handler::new(item.def_loc(), true);
+            p.run_full_visitor(&mut refs);
+            refs.to_locations(p)
+        }
+    };
+    let mut r = Results::default();
+    for v in locations.into_iter() {
+        let e = TextEdit {
+            range: v.range,
+            new_text: new_name.to_string(),
+        };
+        r.insert_edit(v.uri.clone(), e);
+    }
+    Ok(r.edits)
+}
+
+/// Like `rename_edits`,but renaming a `extern` function also
+/// rename the Rust function in `config.rust_dirs`.
+/// Rust edits are found by a textual scan so they need confirmation.
+pub(crate) fn rename_workspace_edit(
+    p: &Project,
+    config: &ServerConfig,
+    fpath: &Url,
+    line: u32,
+    col: u32,
+    new_name: &str,
+) -> Result<WorkspaceEdit, String> {
+    let edits = rename_edits(p, fpath, line, col, new_name)?;
+    let old_name = match renamable(p, fpath, line, col)? {
+        (Item::ExternFunc { name }, _) => name.0,
+        _ => {
+            return Ok(WorkspaceEdit {
+                changes: Some(edits),
+                document_changes: None,
+                change_annotations: None,
+            })
+        }
+    };
+    let rust_edits = rust_fn_edits(p, config, old_name.as_str(), new_name)?;
+    let annotation: ChangeAnnotationIdentifier = "rust".to_string();
+    let mut isle_edits: Vec<_> = edits.into_iter().collect();
+    isle_edits.sort_by(|x, y| x.0.cmp(&y.0));
+    let mut changes = Vec::new();
+    for (uri, edits) in isle_edits.into_iter() {
+        changes.push(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+            edits: edits.into_iter().map(OneOf::Left).collect(),
+        });
+    }
+    for (uri, edits) in rust_edits.into_iter() {
+        changes.push(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+            edits: edits
+                .into_iter()
+                .map(|text_edit| {
+                    OneOf::Right(AnnotatedTextEdit {
+                        text_edit,
+                        annotation_id: annotation.clone(),
+                    })
+                })
+                .collect(),
+        });
+    }
+    let mut change_annotations = HashMap::new();
+    change_annotations.insert(
+        annotation,
+        ChangeAnnotation {
+            label: "Rename Rust function".to_string(),
+            needs_confirmation: Some(true),
+            description: Some(format!(
+                "`{}` -> `{}` in Rust code,found by a textual scan",
+                old_name, new_name
+            )),
+        },
+    );
+    Ok(WorkspaceEdit {
+        changes: None,
+        document_changes: Some(DocumentChanges::Edits(changes)),
+        change_annotations: Some(change_annotations),
+    })
+}
+
+/// Edits on the Rust definition and call sites of `old_name`,sorted by file.
+fn rust_fn_edits(
+    p: &Project,
+    config: &ServerConfig,
+    old_name: &str,
+    new_name: &str,
+) -> Result<Vec<(Url, Vec<TextEdit>)>, String> {
+    let dirs = if !config.rust_dirs.is_empty() {
+        config.rust_dirs.clone()
+    } else {
+        let files: Vec<_> = p
+            .get_filenames()
+            .iter()
+            .map(|x| PathBuf::from(x.as_ref()))
+            .collect();
+        parent_dirs(&files)
+    };
+    let mut ret = Vec::new();
+    for f in rust_files(&dirs).into_iter() {
+        let content = match std::fs::read_to_string(&f) {
+            Ok(x) => x,
+            Err(_) => continue,
+        };
+        if find_fn_refs(content.as_str(), new_name)
+            .iter()
+            .any(|x| x.1 == FnRefKind::Def)
+        {
+            return Err(format!("`fn {}` is already defined in {:?}", new_name, f));
+        }
+        let edits: Vec<_> = find_fn_refs(content.as_str(), old_name)
+            .into_iter()
+            .map(|(range, _)| TextEdit {
+                range,
+                new_text: new_name.to_string(),
+            })
+            .collect();
+        if !edits.is_empty() {
+            if let Ok(u) = Url::from_file_path(&f) {
+                ret.push((u, edits));
+            }
+        }
+    }
+    Ok(ret)
+}
+
+/// Name and position of every `extern` function called `name`.
+fn extern_funcs(p: &Project, name: &str) -> Vec<Ident> {
+    struct FuncsHandler<'a> {
+        name: &'a str,
+        funcs: Vec<Ident>,
+    }
+    impl<'a> ItemOrAccessHandler for FuncsHandler<'a> {
+        fn handle_item_or_access(&mut self, _p: &Project, item: &ItemOrAccess) {
+            match item {
+                ItemOrAccess::Item(Item::ExternFunc { name }) if name.0.as_str() == self.name => {
+                    self.funcs.push(name.clone());
+                }
+                _ => {}
+            }
+        }
+        fn visit_body(&self) -> bool {
+            false
+        }
+        fn finished(&self) -> bool {
+            false
+        }
+    }
+    let mut handler = FuncsHandler {
+        name,
+        funcs: vec![],
+    };
+    p.run_full_visitor(&mut handler);
+    handler.funcs
+}
+
+/// `ISLE` symbol can't contains these.
+fn is_valid_ident(name: &str) -> bool {
+    let first = match name.chars().next() {
+        Some(x) => x,
+        None => return false,
+    };
+    if first.is_ascii_digit() || first == '-' {
+        return false;
+    }
+    if name == "_" || super::KEYWORDS.contains(name) {
+        return false;
+    }
+    name.chars()
+        .all(|c| !(c.is_whitespace() || "();@.$".contains(c)))
+}
+
+fn check_new_name(p: &Project, item: &Item, new_name: &str) -> Result<(), String> {
+    let name = match item {
+        Item::Const { .. } => match new_name.strip_prefix('$') {
+            Some(x) => x,
+            None => {
+                return Err(format!(
+                    "const name must starts with `$`,got `{}`",
+                    new_name
+                ))
+            }
+        },
+        _ => new_name,
+    };
+    if !is_valid_ident(name) {
+        return Err(format!("`{}` is not a valid name", new_name));
+    }
+    if item_name(item).as_str() == new_name {
+        return Ok(());
+    }
+    let clash = |pos: Pos| -> Result<(), String> {
+        let at = p
+            .mk_location(&(pos, 0u32))
+            .map(|x| readable_location(&x))
+            .unwrap_or_default();
+        Err(format!("`{}` is already defined at {}", new_name, at))
+    };
+    match item {
+        Item::Type { .. } | Item::Decl { .. } | Item::Const { .. } => {
+            if let Some(pos) = p.context.query_item(name, |x| x.def_loc().0) {
+                return clash(pos);
+            }
+        }
+        Item::Var { name: var, .. } => {
+            for x in vars_in_same_form(p, &var.1) {
+                if x.0.as_str() == new_name {
+                    return clash(x.1);
+                }
+            }
+            // a var can't shadow a top level item either.
+            if let Some(pos) = p.context.query_item(name, |x| x.def_loc().0) {
+                return clash(pos);
+            }
+        }
+        Item::EnumMemberName { name: variant }
+        | Item::EnumVariant {
+            v: Variant { name: variant, .. },
+        } => {
+            let mut found = None;
+            p.context.all_types(|t| match &t.ty {
+                TypeValue::Primitive(_, _) => {}
+                TypeValue::Enum(vs, _) => {
+                    if vs.iter().any(|v| v.name.1 == variant.1) {
+                        found = vs
+                            .iter()
+                            .find(|v| v.name.0.as_str() == new_name)
+                            .map(|v| v.name.1);
+                    }
+                }
+            });
+            if let Some(pos) = found {
+                return clash(pos);
+            }
+        }
+        Item::EnumMemberField { name: field } => {
+            let mut found = None;
+            p.context.all_types(|t| match &t.ty {
+                TypeValue::Primitive(_, _) => {}
+                TypeValue::Enum(vs, _) => {
+                    for v in vs.iter() {
+                        if v.fields.iter().any(|f| f.name.1 == field.1) {
+                            found = v
+                                .fields
+                                .iter()
+                                .find(|f| f.name.0.as_str() == new_name)
+                                .map(|f| f.name.1);
+                        }
+                    }
+                }
+            });
+            if let Some(pos) = found {
+                return clash(pos);
+            }
+        }
+        Item::ExternFunc { .. } => {
+            if !name.chars().all(|c| c == '_' || c.is_ascii_alphanumeric()) {
+                return Err(format!("`{}` is not a valid Rust function name", new_name));
+            }
+        }
+        Item::Dummy | Item::ConstInt { .. } => {}
+    }
+    Ok(())
+}
+
+/// Variables defined in the top level form contains `pos`.
+fn vars_in_same_form(p: &Project, pos: &Pos) -> Vec<Ident> {
+    let fpath = match p.file_index_path(pos.file) {
+        Some(x) => x,
+        None => return vec![],
+    };
+    let content = p.file_text(&fpath).unwrap_or_default();
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).unwrap_or_default();
+    let form = match forms.iter().find(|x| x.contains_pos(pos)) {
+        Some(x) => x,
+        None => return vec![],
+    };
+    struct VarsHandler<'a> {
+        form: &'a crate::sexpr::SExpr,
+        file: usize,
+        vars: Vec<Ident>,
+    }
+    impl<'a> ItemOrAccessHandler for VarsHandler<'a> {
+        fn handle_item_or_access(&mut self, _p: &Project, item: &ItemOrAccess) {
+            if let ItemOrAccess::Item(Item::Var { name, .. }) = item {
+                if name.1.file == self.file && self.form.contains_pos(&name.1) {
+                    self.vars.push(name.clone());
+                }
+            }
+        }
+        fn visit_body(&self) -> bool {
+            true
+        }
+        fn finished(&self) -> bool {
+            false
+        }
+    }
+    let mut handler = VarsHandler {
+        form,
+        file: pos.file,
+        vars: vec![],
+    };
+    p.run_visitor_for_file(&fpath, &mut handler);
+    handler.vars
+}
+
 #[derive(Default)]
 struct Results {
     edits: HashMap<Url, Vec<TextEdit>>,
@@ -0,0 +1,205 @@
+//! A textual scan of Rust source.
+//! Good enough to find the functions `extern` of `ISLE` bind to
+//! without a Rust parser.
+
+use lsp_types::{Position, Range};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FnRefKind {
+    /// `fn name`
+    Def,
+    /// `name(` or `name::<`
+    Call,
+}
+
+/// Occurrences of function `name` in `content`,comments and strings are skipped.
+pub(crate) fn find_fn_refs(content: &str, name: &str) -> Vec<(Range, FnRefKind)> {
+    let bytes = content.as_bytes();
+    let mut ret = Vec::new();
+    let mut i = 0;
+    // last identifier and where it ends.
+    let mut last_ident: (&str, usize) = ("", 0);
+    let skip_ws = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        i
+    };
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'/' && bytes.get(i + 1) == Some(&b'/') {
+            while i < bytes.len() && bytes[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            let mut depth = 0;
+            while i < bytes.len() {
+                if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+                    depth += 1;
+                    i += 2;
+                } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            continue;
+        }
+        if c == b'"' {
+            i += 1;
+            while i < bytes.len() && bytes[i] != b'"' {
+                if bytes[i] == b'\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i += 1;
+            continue;
+        }
+        if c == b'\'' {
+            // char literal like 'a' or '\n',otherwise a lifetime.
+            if bytes.get(i + 1) == Some(&b'\\') {
+                i += 2;
+                while i < bytes.len() && bytes[i] != b'\'' {
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+            let len = content[i + 1..].chars().next().map(|x| x.len_utf8());
+            if let Some(len) = len {
+                if bytes.get(i + 1 + len) == Some(&b'\'') {
+                    i += len + 2;
+                    continue;
+                }
+            }
+            i += 1;
+            continue;
+        }
+        if c == b'r' && (bytes.get(i + 1) == Some(&b'"') || bytes.get(i + 1) == Some(&b'#')) {
+            let mut hashes = 0;
+            let mut j = i + 1;
+            while bytes.get(j) == Some(&b'#') {
+                hashes += 1;
+                j += 1;
+            }
+            if bytes.get(j) == Some(&b'"') {
+                let mut end = String::from("\"");
+                end.push_str("#".repeat(hashes).as_str());
+                i = match content[j + 1..].find(end.as_str()) {
+                    Some(x) => j + 1 + x + end.len(),
+                    None => bytes.len(),
+                };
+                continue;
+            }
+        }
+        if c == b'_' || c.is_ascii_alphabetic() {
+            let start = i;
+            while i < bytes.len() && (bytes[i] == b'_' || bytes[i].is_ascii_alphanumeric()) {
+                i += 1;
+            }
+            let ident = &content[start..i];
+            if ident == name {
+                let next = skip_ws(i);
+                let after_fn = last_ident.0 == "fn" && skip_ws(last_ident.1) == start;
+                let kind = if after_fn {
+                    Some(FnRefKind::Def)
+                } else if bytes.get(next) == Some(&b'(') || content[next..].starts_with("::<") {
+                    Some(FnRefKind::Call)
+                } else {
+                    None
+                };
+                if let Some(kind) = kind {
+                    ret.push((
+                        Range {
+                            start: offset_to_position(content, start),
+                            end: offset_to_position(content, i),
+                        },
+                        kind,
+                    ));
+                }
+            }
+            last_ident = (ident, i);
+            continue;
+        }
+        i += 1;
+    }
+    ret
+}
+
+/// `LSP` position of a byte offset,column is in UTF-16.
+fn offset_to_position(content: &str, offset: usize) -> Position {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map(|x| x + 1).unwrap_or(0);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+/// All `.rs` files under `dirs`.
+pub(crate) fn rust_files(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut ret = Vec::new();
+    for d in dirs.iter() {
+        for x in walkdir::WalkDir::new(d) {
+            let x = match x {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            if x.file_type().is_file() && x.path().extension().map(|e| e == "rs").unwrap_or(false) {
+                ret.push(x.path().to_path_buf());
+            }
+        }
+    }
+    ret.sort();
+    ret.dedup();
+    ret
+}
+
+/// Directories of `files` without the ones inside another one.
+pub(crate) fn parent_dirs<P: AsRef<Path>>(files: &[P]) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = files
+        .iter()
+        .filter_map(|x| x.as_ref().parent().map(|x| x.to_path_buf()))
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    let all = dirs.clone();
+    dirs.retain(|d| !all.iter().any(|x| x != d && d.starts_with(x)));
+    dirs
+}
+
+#[cfg(test)]
+#[test]
+fn test_find_fn_refs() {
+    let content = r#"impl Context for IsleContext {
+    // my_fn(1) in comment
+    fn my_fn(&mut self, x: u32) -> u32 {
+        let s = "my_fn(";
+        let c = '"';
+        self.my_fn_2(x) + other::my_fn::<u32>(x)
+    }
+    fn f<'a>(&'a self) { self.my_fn (1); let my_fn = 1; }
+}
+"#;
+    let refs = find_fn_refs(content, "my_fn");
+    let got: Vec<_> = refs
+        .iter()
+        .map(|(r, k)| (r.start.line, r.start.character, r.end.character, *k))
+        .collect();
+    assert_eq!(
+        got,
+        vec![
+            (2, 7, 12, FnRefKind::Def),
+            (5, 33, 38, FnRefKind::Call),
+            (7, 30, 35, FnRefKind::Call),
+        ]
+    );
+}
@@ -0,0 +1,82 @@
+use super::context::*;
+use crate::sexpr::{enclosing, parse_sexprs, position_to_offset, SExpr};
+use lsp_server::*;
+use lsp_types::*;
+
+/// Handles selection range request of the language server.
+pub fn on_selection_range_request(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<SelectionRangeParams>(request.params.clone())
+        .expect("could not deserialize selection range request");
+    let fpath = parameters.text_document.uri.to_file_path().unwrap();
+    let content = context.project.file_text(&fpath).unwrap_or_default();
+    let forms = parse_sexprs(content.as_str(), fpath.to_str().unwrap()).unwrap_or_default();
+    let ranges: Vec<_> = parameters
+        .positions
+        .iter()
+        .map(|p| selection_range(&forms, content.as_str(), p))
+        .collect();
+    let r = Response::new_ok(
+        request.id.clone(),
+        serde_json::to_value(Some(ranges)).unwrap(),
+    );
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
+
+/// From the token under `p` to the top level form.
+fn selection_range(forms: &[SExpr], content: &str, p: &Position) -> SelectionRange {
+    let offset = position_to_offset(content, p);
+    let mut ret: Option<SelectionRange> = None;
+    for e in enclosing(forms, offset) {
+        ret = Some(SelectionRange {
+            range: e.range(),
+            parent: ret.map(Box::new),
+        });
+    }
+    ret.unwrap_or(SelectionRange {
+        range: Range { start: *p, end: *p },
+        parent: None,
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn test_selection_range() {
+    let content = "(decl A (u32) u32)\n(rule (A x)\n  (B x))\n";
+    let forms = parse_sexprs(content, "").unwrap();
+    let r = selection_range(
+        &forms,
+        content,
+        &Position {
+            line: 2,
+            character: 6,
+        },
+    );
+    let mut ranges = vec![];
+    let mut r = Some(Box::new(r));
+    while let Some(x) = r {
+        ranges.push(x.range);
+        r = x.parent;
+    }
+    let mk_range = |l1, c1, l2, c2| Range {
+        start: Position {
+            line: l1,
+            character: c1,
+        },
+        end: Position {
+            line: l2,
+            character: c2,
+        },
+    };
+    assert_eq!(
+        ranges,
+        vec![
+            mk_range(2, 5, 2, 6),
+            mk_range(2, 2, 2, 7),
+            mk_range(1, 0, 2, 8)
+        ]
+    );
+}
@@ -0,0 +1,165 @@
+//! Types resolved by `cranelift_isle::sema`,mapped back to source positions.
+//! Built for each snapshot of the project that type checks,
+//! the visitor of `project_visit.rs` guesses when it does not.
+
+use crate::project::without_false_defs;
+use cranelift_isle::ast::{Def, Defs, Expr, Ident, Pattern, Rule};
+use cranelift_isle::lexer::Pos;
+use cranelift_isle::sema::{self, TermEnv, TypeEnv};
+use std::collections::HashMap;
+
+pub struct SemaInfo {
+    pub(crate) tyenv: TypeEnv,
+    pub(crate) termenv: TermEnv,
+    /// Where a variable is bound -> its type,with the position of the type definition.
+    var_types: HashMap<(usize, usize, usize), Ident>,
+}
+
+impl SemaInfo {
+    /// `None` if `defs` does not type check.
+    pub(crate) fn new(defs: &Defs) -> Option<Self> {
+        let defs = without_false_defs(defs);
+        let mut tyenv = TypeEnv::from_ast(&defs).ok()?;
+        let termenv = TermEnv::from_ast(&mut tyenv, &defs).ok()?;
+        let rules: HashMap<_, _> = termenv.rules.iter().map(|r| (key(&r.pos), r)).collect();
+        let mut var_types = HashMap::new();
+        for d in defs.defs.iter() {
+            let r = match d {
+                Def::Rule(r) => r,
+                _ => continue,
+            };
+            if let Some(s) = rules.get(&key(&r.pos)) {
+                for (var, ty) in bindings(&tyenv, r, s).into_iter() {
+                    var_types.insert(key(&var.1), type_ident(&tyenv, ty));
+                }
+            }
+        }
+        Some(Self {
+            tyenv,
+            termenv,
+            var_types,
+        })
+    }
+
+    /// Type of the variable bound at `var`.
+    pub(crate) fn var_type(&self, var: &Ident) -> Option<&Ident> {
+        self.var_types.get(&key(&var.1))
+    }
+}
+
+fn key(pos: &Pos) -> (usize, usize, usize) {
+    (pos.file, pos.line, pos.col)
+}
+
+fn type_ident(tyenv: &TypeEnv, ty: sema::TypeId) -> Ident {
+    let t = &tyenv.types[ty.index()];
+    let pos = match t {
+        sema::Type::Primitive(_, _, pos) => *pos,
+        sema::Type::Enum { pos, .. } => *pos,
+    };
+    Ident(t.name(tyenv).to_string(), pos)
+}
+
+/// Where each variable of `s` is bound in `r`,with its type.
+/// `sema` keeps no positions of variables,so both are walked in the order `sema` binds them,
+/// a variable bound in the source is paired with the first unpaired one has the same name.
+fn bindings(tyenv: &TypeEnv, r: &Rule, s: &sema::Rule) -> Vec<(Ident, sema::TypeId)> {
+    let mut ids = Vec::new();
+    s.args.iter().for_each(|x| sema_pattern_vars(x, &mut ids));
+    for i in s.iflets.iter() {
+        sema_expr_vars(&i.rhs, &mut ids);
+        sema_pattern_vars(&i.lhs, &mut ids);
+    }
+    sema_expr_vars(&s.rhs, &mut ids);
+    let mut idents = Vec::new();
+    let mut bound = Vec::new();
+    ast_pattern_vars(&r.pattern, &mut bound, &mut idents);
+    for i in r.iflets.iter() {
+        ast_expr_vars(&i.expr, &mut idents);
+        ast_pattern_vars(&i.pattern, &mut bound, &mut idents);
+    }
+    ast_expr_vars(&r.expr, &mut idents);
+    let mut ret = Vec::new();
+    for ident in idents.into_iter() {
+        let index = ids.iter().position(|id| {
+            s.vars
+                .get(id.index())
+                .is_some_and(|v| tyenv.syms[v.name.index()] == ident.0)
+        });
+        if let Some(index) = index {
+            let id = ids.remove(index);
+            ret.push((ident, s.vars[id.index()].ty));
+        }
+    }
+    ret
+}
+
+/// Variables bound by `pat`,`sema` binds a variable where it first appears.
+fn sema_pattern_vars(pat: &sema::Pattern, ids: &mut Vec<sema::VarId>) {
+    match pat {
+        sema::Pattern::BindPattern(_, id, subpat) => {
+            ids.push(*id);
+            sema_pattern_vars(subpat.as_ref(), ids);
+        }
+        sema::Pattern::Term(_, _, subpats) | sema::Pattern::And(_, subpats) => {
+            subpats.iter().for_each(|x| sema_pattern_vars(x, ids))
+        }
+        sema::Pattern::Var(..)
+        | sema::Pattern::ConstInt(..)
+        | sema::Pattern::ConstPrim(..)
+        | sema::Pattern::Wildcard(..) => {}
+    }
+}
+
+fn sema_expr_vars(e: &sema::Expr, ids: &mut Vec<sema::VarId>) {
+    match e {
+        sema::Expr::Term(_, _, args) => args.iter().for_each(|x| sema_expr_vars(x, ids)),
+        sema::Expr::Let { bindings, body, .. } => {
+            for (id, _, val) in bindings.iter() {
+                sema_expr_vars(val.as_ref(), ids);
+                ids.push(*id);
+            }
+            sema_expr_vars(body.as_ref(), ids);
+        }
+        sema::Expr::Var(..) | sema::Expr::ConstInt(..) | sema::Expr::ConstPrim(..) => {}
+    }
+}
+
+/// Variables bound by `pat`,a name already in `bound` is a equality check.
+fn ast_pattern_vars(pat: &Pattern, bound: &mut Vec<String>, idents: &mut Vec<Ident>) {
+    match pat {
+        Pattern::Var { var, .. } => {
+            if !bound.contains(&var.0) {
+                bound.push(var.0.clone());
+                idents.push(var.clone());
+            }
+        }
+        Pattern::BindPattern { var, subpat, .. } => {
+            bound.push(var.0.clone());
+            idents.push(var.clone());
+            ast_pattern_vars(subpat.as_ref(), bound, idents);
+        }
+        Pattern::Term { args, .. } => args.iter().for_each(|x| ast_pattern_vars(x, bound, idents)),
+        Pattern::And { subpats, .. } => subpats
+            .iter()
+            .for_each(|x| ast_pattern_vars(x, bound, idents)),
+        Pattern::ConstInt { .. }
+        | Pattern::ConstPrim { .. }
+        | Pattern::Wildcard { .. }
+        | Pattern::MacroArg { .. } => {}
+    }
+}
+
+fn ast_expr_vars(e: &Expr, idents: &mut Vec<Ident>) {
+    match e {
+        Expr::Term { args, .. } => args.iter().for_each(|x| ast_expr_vars(x, idents)),
+        Expr::Let { defs, body, .. } => {
+            for d in defs.iter() {
+                ast_expr_vars(d.val.as_ref(), idents);
+                idents.push(d.var.clone());
+            }
+            ast_expr_vars(body.as_ref(), idents);
+        }
+        Expr::Var { .. } | Expr::ConstInt { .. } | Expr::ConstPrim { .. } => {}
+    }
+}
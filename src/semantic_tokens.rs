@@ -1,38 +1,148 @@
-use std::{cmp::Ordering, path::PathBuf};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
 
-use crate::project::AstProvider;
-use crate::project::RefVecDefAstProvider;
+use crate::item::*;
+use crate::project::{get_rule_target, AstProvider, ItemOrAccessHandler, Project};
 
 use super::context::*;
 use super::to_lsp_range;
-use cranelift_isle::ast::Pattern;
-use cranelift_isle::parser::parse;
 use cranelift_isle::{
     ast::*,
     error::Errors,
-    lexer::{Lexer, Token},
+    lexer::{Lexer, Pos, Token},
 };
 use lsp_server::*;
 use lsp_types::*;
 
-/// Handles semantic tokens request for LSP server.
-pub fn on_senantic_tokens(context: &Context, request: &Request) {
+/// Last result sent to client for every document,used to compute delta.
+#[derive(Default)]
+pub struct SemanticTokensCache {
+    next_id: u64,
+    results: HashMap<Url, (String, Vec<SemanticToken>)>,
+}
+
+impl SemanticTokensCache {
+    fn insert(&mut self, uri: Url, tokens: Vec<SemanticToken>) -> String {
+        self.next_id += 1;
+        let id = self.next_id.to_string();
+        self.results.insert(uri, (id.clone(), tokens));
+        id
+    }
+}
+
+/// Legend of token types and modifiers,index of them are what we send to client.
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: enum_iterator::all::<TokenTypes>()
+            .map(|x| SemanticTokenType::new(x.to_static_str()))
+            .collect(),
+        token_modifiers: enum_iterator::all::<TokenModifier>()
+            .map(|x| SemanticTokenModifier::new(x.to_static_str()))
+            .collect(),
+    }
+}
+
+/// Handles semantic tokens full request for LSP server.
+pub fn on_semantic_tokens_full(context: &mut Context, request: &Request) {
     let parameters = serde_json::from_value::<SemanticTokensParams>(request.params.clone())
         .expect("could not deserialize semantic tokens request");
+    let uri = parameters.text_document.uri;
+    let results = full_tokens(context, &uri).map(|(id, data)| {
+        SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: Some(id),
+            data,
+        })
+    });
+    let r = Response::new_ok(request.id.clone(), serde_json::to_value(results).unwrap());
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
+
+/// Handles semantic tokens full delta request for LSP server.
+pub fn on_semantic_tokens_full_delta(context: &mut Context, request: &Request) {
+    let parameters = serde_json::from_value::<SemanticTokensDeltaParams>(request.params.clone())
+        .expect("could not deserialize semantic tokens delta request");
+    let uri = parameters.text_document.uri;
+    let previous = context
+        .semantic_tokens
+        .results
+        .get(&uri)
+        .filter(|x| x.0 == parameters.previous_result_id)
+        .map(|x| x.1.clone());
+    let results = full_tokens(context, &uri).map(|(id, data)| match previous {
+        Some(previous) => SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+            result_id: Some(id),
+            edits: delta_edits(&previous, &data),
+        }),
+        None => SemanticTokensFullDeltaResult::Tokens(SemanticTokens {
+            result_id: Some(id),
+            data,
+        }),
+    });
+    let r = Response::new_ok(request.id.clone(), serde_json::to_value(results).unwrap());
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
+
+/// Handles semantic tokens range request for LSP server.
+pub fn on_semantic_tokens_range(context: &Context, request: &Request) {
+    let parameters = serde_json::from_value::<SemanticTokensRangeParams>(request.params.clone())
+        .expect("could not deserialize semantic tokens range request");
     let fpath = parameters.text_document.uri.to_file_path().unwrap();
-    let lexer = Lexer::from_files(vec![fpath.clone()]).unwrap();
-    let asts = match parse(lexer) {
-        Ok(x) => x,
-        Err(_) => return,
-    };
-    let asts = RefVecDefAstProvider { defs: &asts.defs };
-    let mut collector = AstSemanticTokenCollector::new();
-    asts.with_def(|d| collector.collect_def(d));
-    let mut tokens = collector.to_tokens();
-    tokens.extend(match collect_keywords(&fpath) {
-        Ok(x) => x,
-        Err(_) => vec![],
+    let results = range_tokens(&context.project, &fpath)
+        .map(|tokens| tokens_in_range(tokens, &parameters.range))
+        .map(|tokens| {
+            SemanticTokensRangeResult::Tokens(SemanticTokens {
+                result_id: None,
+                data: encode(tokens),
+            })
+        });
+    let r = Response::new_ok(request.id.clone(), serde_json::to_value(results).unwrap());
+    context
+        .connection
+        .sender
+        .send(Message::Response(r))
+        .unwrap();
+}
+
+/// Tokens of the in-memory buffer and the new result id.
+/// Previous result is used if file is not part of the project.
+fn full_tokens(context: &mut Context, uri: &Url) -> Option<(String, Vec<SemanticToken>)> {
+    let fpath = uri.to_file_path().unwrap();
+    match range_tokens(&context.project, &fpath) {
+        Some(tokens) => {
+            let data = encode(tokens);
+            let id = context.semantic_tokens.insert(uri.clone(), data.clone());
+            Some((id, data))
+        }
+        None => context.semantic_tokens.results.get(uri).cloned(),
+    }
+}
+
+/// Tokens sorted by position,`None` if `fpath` is not part of the project.
+/// Defs of the project are updated on every change of the buffer,
+/// so this is the last version of buffer can be parsed.
+fn range_tokens(p: &Project, fpath: &PathBuf) -> Option<Vec<RangeToken>> {
+    let file = p.found_file_index(fpath)?;
+    let content = p.file_text(fpath)?;
+    let mut handler = Handler::new(file);
+    p.found_file_defs(fpath)?.with_rule(|r| {
+        if let Some((_, pos)) = get_rule_target(&r.pattern) {
+            handler.rule_roots.insert(pos);
+        }
     });
+    p.run_visitor_for_file(fpath, &mut handler);
+    let mut tokens = handler.to_tokens(p);
+    tokens.extend(collect_keywords(content.as_str(), fpath.to_str().unwrap()).unwrap_or_default());
     tokens.sort_by(|a, b| {
         let o = a.range.start.line.cmp(&b.range.start.line);
         if o == Ordering::Equal {
@@ -41,35 +151,54 @@ pub fn on_senantic_tokens(context: &Context, request: &Request) {
             o
         }
     });
-    let tokens = {
-        let mut v = VecST::new();
-        for t in tokens.into_iter() {
-            v.push_back(t.range, t.token_type, t.modifiers);
-        }
-        v
-    };
-    let results = SemanticTokensResult::Tokens(SemanticTokens {
-        result_id: None,
-        data: tokens.to_tokens(),
-    });
-    let r = Response::new_ok(
-        request.id.clone(),
-        serde_json::to_value(Some(results)).unwrap(),
-    );
-    context
-        .connection
-        .sender
-        .send(Message::Response(r))
-        .unwrap();
+    tokens.dedup_by(|a, b| a.range.start == b.range.start);
+    Some(tokens)
 }
 
-struct AstSemanticTokenCollector {
-    results: Vec<RangeToken>,
+/// Tokens overlap with `range`.
+fn tokens_in_range(tokens: Vec<RangeToken>, range: &Range) -> Vec<RangeToken> {
+    tokens
+        .into_iter()
+        .filter(|t| t.range.end > range.start && t.range.start < range.end)
+        .collect()
 }
 
-fn collect_keywords(path: &PathBuf) -> Result<Vec<RangeToken>, Errors> {
-    let content = std::fs::read_to_string(path.as_path()).unwrap();
-    let mut lexer = Lexer::from_str(content.as_str(), path.as_path().to_str().unwrap())?;
+fn encode(tokens: Vec<RangeToken>) -> Vec<SemanticToken> {
+    let mut v = VecST::new();
+    for t in tokens.into_iter() {
+        v.push_back(t.range, t.token_type, Some(t.modifiers));
+    }
+    v.to_tokens()
+}
+
+/// Edits turn `old` into `new`,only the part between common prefix and suffix changed.
+fn delta_edits(old: &[SemanticToken], new: &[SemanticToken]) -> Vec<SemanticTokensEdit> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let deleted = old.len() - prefix - suffix;
+    let inserted = &new[prefix..new.len() - suffix];
+    if deleted == 0 && inserted.len() == 0 {
+        return vec![];
+    }
+    // every token is 5 integers.
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: (deleted * 5) as u32,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+fn collect_keywords(content: &str, filename: &str) -> Result<Vec<RangeToken>, Errors> {
+    let mut lexer = Lexer::from_str(content, filename)?;
     let mut ret = Vec::new();
     while let Some((pos, t)) = lexer.next()? {
         match t {
@@ -78,7 +207,7 @@ fn collect_keywords(path: &PathBuf) -> Result<Vec<RangeToken>, Errors> {
                     ret.push(RangeToken {
                         range: to_lsp_range(&(pos, s.len() as u32)),
                         token_type: TokenTypes::Keyword,
-                        modifiers: None,
+                        modifiers: 0,
                     });
                 }
             }
@@ -91,272 +220,154 @@ fn collect_keywords(path: &PathBuf) -> Result<Vec<RangeToken>, Errors> {
 struct RangeToken {
     range: Range,
     token_type: TokenTypes,
-    modifiers: Option<TokenModifier>,
+    /// bitset of `TokenModifier`.
+    modifiers: u32,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-enum CollectPatternType {
-    Extrator,
-    Matcher,
+/// Collect tokens from the resolved items of a file.
+struct Handler {
+    file: usize,
+    /// Root terms of rules,which are definitions of the term.
+    rule_roots: HashSet<Pos>,
+    results: Vec<RangeToken>,
+    /// Kind of a decl is only known after all rules visited.
+    decls: Vec<Ident>,
 }
 
-impl CollectPatternType {
-    fn to_modifier(self) -> Option<TokenModifier> {
-        match self {
-            CollectPatternType::Extrator => None,
-            CollectPatternType::Matcher => Some(TokenModifier::Declaration),
+impl Handler {
+    fn new(file: usize) -> Self {
+        Self {
+            file,
+            rule_roots: Default::default(),
+            results: vec![],
+            decls: vec![],
         }
     }
-}
 
-impl AstSemanticTokenCollector {
-    fn new() -> Self {
-        Self { results: vec![] }
-    }
-    fn to_tokens(self) -> Vec<RangeToken> {
-        self.results
-    }
-    fn collect_def(&mut self, d: &Def) {
-        match d {
-            Def::Pragma(_) => {}
-            Def::Type(x) => self.collect_type(x),
-            Def::Rule(x) => self.collect_rule(x),
-            Def::Extractor(x) => self.collect_extractor(x),
-            Def::Decl(x) => self.collect_decl(x),
-            Def::Extern(x) => self.collect_extern(x),
-            Def::Converter(x) => self.collect_converter(x),
+    fn push(&mut self, pos: Pos, len: usize, token_type: TokenTypes, modifiers: u32) {
+        if pos.file != self.file {
+            return;
         }
-    }
-
-    fn collect_type(&mut self, d: &Type) {
         self.results.push(RangeToken {
-            range: to_lsp_range(&d.name),
-            token_type: TokenTypes::Type,
-            modifiers: Some(TokenModifier::Declaration),
+            range: to_lsp_range(&(pos, len as u32)),
+            token_type,
+            modifiers,
         });
-        match &d.ty {
-            TypeValue::Primitive(x, _) => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(x),
-                    token_type: TokenTypes::Type,
-                    modifiers: None,
-                });
-            }
-            TypeValue::Enum(enums, _) => {
-                for v in enums.iter() {
-                    self.results.push(RangeToken {
-                        range: to_lsp_range(&v.name),
-                        token_type: TokenTypes::EnumMember,
-                        modifiers: Some(TokenModifier::Declaration),
-                    });
-                    for f in v.fields.iter() {
-                        self.results.push(RangeToken {
-                            range: to_lsp_range(&f.name),
-                            token_type: TokenTypes::EnumMember,
-                            modifiers: Some(TokenModifier::Declaration),
-                        });
-                        self.results.push(RangeToken {
-                            range: to_lsp_range(&f.ty),
-                            token_type: TokenTypes::Type,
-                            modifiers: None,
-                        });
-                    }
-                }
-            }
-        }
     }
 
-    fn collect_rule(&mut self, d: &Rule) {
-        self.collect_pattern(&d.pattern, CollectPatternType::Matcher);
-        for i in d.iflets.iter() {
-            self.collect_pattern(&i.pattern, CollectPatternType::Matcher);
-            self.collect_expr(&i.expr);
+    fn to_tokens(mut self, p: &Project) -> Vec<RangeToken> {
+        for name in std::mem::take(&mut self.decls).into_iter() {
+            if let Item::Decl { decl, kind } = p.context.query_item_clone(&name.0) {
+                let (tt, m) = decl_token(&decl, kind);
+                self.push(
+                    name.1,
+                    name.0.len(),
+                    tt,
+                    m | TokenModifier::Declaration.to_u32(),
+                );
+            }
         }
-        self.collect_expr(&d.expr);
+        self.results
     }
+}
 
-    fn collect_expr(&mut self, e: &Expr) {
-        match e {
-            Expr::Term { sym, args, .. } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(sym),
-                    token_type: TokenTypes::Variable,
-                    modifiers: None,
-                });
-                for a in args.iter() {
-                    self.collect_expr(a);
-                }
-            }
-            Expr::Var { name, .. } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(name),
-                    token_type: TokenTypes::Variable,
-                    modifiers: None,
-                });
-            }
-            Expr::ConstInt { val: _, pos: _ } => {}
-            Expr::ConstPrim { val, pos: _ } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(val),
-                    token_type: TokenTypes::String,
-                    modifiers: None,
-                });
-            }
-
-            Expr::Let { defs, body, .. } => {
-                for d in defs.iter() {
-                    self.results.push(RangeToken {
-                        range: to_lsp_range(&d.var),
-                        token_type: TokenTypes::Number,
-                        modifiers: Some(TokenModifier::Declaration),
-                    });
-                    self.results.push(RangeToken {
-                        range: to_lsp_range(&d.ty),
-                        token_type: TokenTypes::Type,
-                        modifiers: Some(TokenModifier::Declaration),
-                    });
-                }
-                self.collect_expr(body.as_ref());
-            }
-        }
+/// Token type and modifiers of a term.
+fn decl_token(decl: &Decl, kind: DeclKind) -> (TokenTypes, u32) {
+    let tt = match (
+        kind.has(DeclKind::EXTRATOR),
+        kind.has(DeclKind::CONSTRUCTOR),
+    ) {
+        (true, false) => TokenTypes::Extractor,
+        (false, true) => TokenTypes::Constructor,
+        _ => TokenTypes::Term,
+    };
+    let mut m = 0;
+    if decl.pure {
+        m |= TokenModifier::Readonly.to_u32();
     }
-    fn collect_extractor(&mut self, d: &Extractor) {
-        self.results.push(RangeToken {
-            range: to_lsp_range(&d.term),
-            token_type: TokenTypes::Function,
-            modifiers: None,
-        });
-        for a in d.args.iter() {
-            self.results.push(RangeToken {
-                range: to_lsp_range(a),
-                token_type: TokenTypes::Variable,
-                modifiers: Some(TokenModifier::Declaration),
-            });
-        }
-        self.collect_pattern(&d.template, CollectPatternType::Extrator);
+    if kind.has(DeclKind::EXTERN) {
+        m |= TokenModifier::Extern.to_u32();
     }
+    (tt, m)
+}
 
-    fn collect_pattern(&mut self, p: &Pattern, mode: CollectPatternType) {
-        match p {
-            Pattern::Var { var, .. } => self.results.push(RangeToken {
-                range: to_lsp_range(var),
-                token_type: TokenTypes::Variable,
-                modifiers: mode.to_modifier(),
-            }),
-            Pattern::BindPattern {
-                var,
-                subpat,
-                pos: _,
-            } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(var),
-                    token_type: TokenTypes::Variable,
-                    modifiers: None,
-                });
-                self.collect_pattern(subpat.as_ref(), mode);
-            }
-            Pattern::ConstInt { val: _, pos } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(&(pos.clone(), 0 as u32)),
-                    token_type: TokenTypes::Number,
-                    modifiers: None,
-                });
-            }
-            Pattern::ConstPrim { val, .. } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(val),
-                    token_type: TokenTypes::String,
-                    modifiers: None,
-                });
-            }
-            Pattern::Term { sym, args, pos: _ } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(sym),
-                    token_type: TokenTypes::Variable,
-                    modifiers: None,
-                });
-                for a in args.iter() {
-                    self.collect_pattern(a, mode);
+impl ItemOrAccessHandler for Handler {
+    fn handle_item_or_access(&mut self, _p: &Project, item: &ItemOrAccess) {
+        let declaration = TokenModifier::Declaration.to_u32();
+        let readonly = TokenModifier::Readonly.to_u32();
+        match item {
+            ItemOrAccess::Item(item) => match item {
+                Item::Type { ty } => {
+                    self.push(ty.name.1, ty.name.0.len(), TokenTypes::Type, declaration)
                 }
-            }
-            Pattern::Wildcard { pos: _ } => {}
-            Pattern::And { subpats, pos: _ } => {
-                for s in subpats.iter() {
-                    self.collect_pattern(s, mode);
+                Item::Decl { decl, .. } => self.decls.push(decl.term.clone()),
+                Item::Const { name, .. } => self.push(
+                    name.1,
+                    name.0.len() + 1,
+                    TokenTypes::Variable,
+                    declaration | readonly,
+                ),
+                Item::Var { name, .. } => {
+                    self.push(name.1, name.0.len(), TokenTypes::Variable, declaration)
+                }
+                Item::EnumMemberName { name } => {
+                    self.push(name.1, name.0.len(), TokenTypes::EnumMember, declaration)
                 }
+                Item::EnumMemberField { name } => {
+                    self.push(name.1, name.0.len(), TokenTypes::Property, declaration)
+                }
+                Item::ConstInt { lit, .. } => self.push(lit.1, lit.0.len(), TokenTypes::Number, 0),
+                Item::ExternFunc { name } => self.push(
+                    name.1,
+                    name.0.len(),
+                    TokenTypes::Function,
+                    TokenModifier::Extern.to_u32(),
+                ),
+                Item::Dummy | Item::EnumVariant { .. } => {}
+            },
+            ItemOrAccess::Access(access) => {
+                let pos = access.access.1;
+                let len = access.access.0.len();
+                let (tt, m) = match &access.def {
+                    Item::Dummy => (TokenTypes::Unresolved, 0),
+                    // a variant not found has the position of access.
+                    Item::EnumVariant { v } if v.name.1 == pos => (TokenTypes::Unresolved, 0),
+                    Item::EnumVariant { .. } | Item::EnumMemberName { .. } => {
+                        (TokenTypes::EnumMember, 0)
+                    }
+                    Item::Decl { decl, kind } => {
+                        let (tt, mut m) = decl_token(decl, *kind);
+                        let definition = match access.kind {
+                            AccessKind::DeclExtern
+                            | AccessKind::ImplExtractor
+                            | AccessKind::ImplConstructor => true,
+                            _ => self.rule_roots.contains(&pos),
+                        };
+                        if definition {
+                            m |= TokenModifier::Definition.to_u32();
+                        }
+                        (tt, m)
+                    }
+                    Item::Type { .. } => (TokenTypes::Type, 0),
+                    Item::Var { .. } => (TokenTypes::Variable, 0),
+                    Item::Const { .. } => {
+                        // `$` not in name.
+                        self.push(pos, len + 1, TokenTypes::Variable, readonly);
+                        return;
+                    }
+                    Item::EnumMemberField { .. } => (TokenTypes::Property, 0),
+                    Item::ConstInt { .. } => (TokenTypes::Number, 0),
+                    Item::ExternFunc { .. } => (TokenTypes::Function, 0),
+                };
+                self.push(pos, len, tt, m);
             }
-            Pattern::MacroArg { .. } => {}
-        }
-    }
-
-    fn collect_decl(&mut self, d: &Decl) {
-        self.results.push(RangeToken {
-            range: to_lsp_range(&d.term),
-            token_type: TokenTypes::Struct,
-            modifiers: Some(TokenModifier::Declaration),
-        });
-        for t in d.arg_tys.iter() {
-            self.results.push(RangeToken {
-                range: to_lsp_range(t),
-                token_type: TokenTypes::Type,
-                modifiers: None,
-            });
         }
-        self.results.push(RangeToken {
-            range: to_lsp_range(&d.ret_ty),
-            token_type: TokenTypes::Type,
-            modifiers: None,
-        });
     }
-
-    fn collect_extern(&mut self, d: &Extern) {
-        match d {
-            Extern::Extractor { term, .. } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(term),
-                    token_type: TokenTypes::Function,
-                    modifiers: None,
-                });
-            }
-            Extern::Constructor { term, .. } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(term),
-                    token_type: TokenTypes::Function,
-                    modifiers: None,
-                });
-            }
-            Extern::Const { name, ty, .. } => {
-                self.results.push(RangeToken {
-                    range: to_lsp_range(name),
-                    token_type: TokenTypes::Variable,
-                    modifiers: None,
-                });
-                self.results.push(RangeToken {
-                    range: to_lsp_range(ty),
-                    token_type: TokenTypes::Type,
-                    modifiers: None,
-                });
-            }
-        }
+    fn visit_body(&self) -> bool {
+        true
     }
-
-    fn collect_converter(&mut self, d: &Converter) {
-        self.results.push(RangeToken {
-            range: to_lsp_range(&d.term),
-            token_type: TokenTypes::Function,
-            modifiers: Some(TokenModifier::Declaration),
-        });
-        self.results.push(RangeToken {
-            range: to_lsp_range(&d.inner_ty),
-            token_type: TokenTypes::Type,
-            modifiers: None,
-        });
-
-        self.results.push(RangeToken {
-            range: to_lsp_range(&d.outer_ty),
-            token_type: TokenTypes::Type,
-            modifiers: None,
-        });
+    fn finished(&self) -> bool {
+        false
     }
 }
 
@@ -371,10 +382,18 @@ pub enum TokenTypes {
     EnumMember,
     Type,
     Number,
+    Property,
+    /// Term only has extractor.
+    Extractor,
+    /// Term only has constructor.
+    Constructor,
+    /// Term has both or none of them.
+    Term,
+    /// Name can't be resolved,typos stand out before save.
+    Unresolved,
 }
 
 impl TokenTypes {
-    #[allow(dead_code)]
     fn to_static_str(self) -> &'static str {
         match self {
             TokenTypes::Struct => "struct",
@@ -386,6 +405,11 @@ impl TokenTypes {
             TokenTypes::EnumMember => "enumMember",
             TokenTypes::Type => "type",
             TokenTypes::Number => "number",
+            TokenTypes::Property => "property",
+            TokenTypes::Extractor => "extractor",
+            TokenTypes::Constructor => "constructor",
+            TokenTypes::Term => "term",
+            TokenTypes::Unresolved => "unresolved",
         }
     }
     fn to_u32(self) -> u32 {
@@ -416,6 +440,12 @@ fn ts_code() {
 #[derive(Debug, Clone, Copy, enum_iterator::Sequence)]
 pub enum TokenModifier {
     Declaration,
+    /// Root of a rule,extractor or extern.
+    Definition,
+    /// `pure` term or const.
+    Readonly,
+    /// Implemented by Rust code.
+    Extern,
 }
 
 impl Into<u32> for TokenModifier {
@@ -425,14 +455,16 @@ impl Into<u32> for TokenModifier {
 }
 
 impl TokenModifier {
-    #[allow(dead_code)] // actual used in test.
     fn to_static_str(self) -> &'static str {
         match self {
             Self::Declaration => "declaration",
+            Self::Definition => "definition",
+            Self::Readonly => "readonly",
+            Self::Extern => "extern",
         }
     }
     fn to_u32(self) -> u32 {
-        self as u32
+        1 << (self as u32)
     }
 }
 
@@ -461,7 +493,7 @@ impl VecST {
         );
         let tt = tt.into();
         let mid = mid.map(|x| x.into()).unwrap_or_default();
-        if self.tokens.len() > 0 {
+        if !self.tokens.is_empty() {
             if self.last_line == range.start.line {
                 self.tokens.push(SemanticToken {
                     delta_line: 0,
@@ -554,7 +586,6 @@ mod test_vec_st {
         ];
         let mut v = VecST::new();
         for s in ss.iter() {
-            eprintln!("range:{:?}", s.to_range());
             v.push_back(s.to_range(), s.token_type, Some(s.token_modifiers));
         }
         let tokens = v.to_tokens();
@@ -586,3 +617,129 @@ mod test_vec_st {
         )
     }
 }
+
+#[cfg(test)]
+#[test]
+fn test_delta_edits() {
+    let t = |x: u32| SemanticToken {
+        delta_line: x,
+        delta_start: 0,
+        length: 1,
+        token_type: 0,
+        token_modifiers_bitset: 0,
+    };
+    let old = vec![t(1), t(2), t(3), t(4)];
+    let new = vec![t(1), t(5), t(6), t(4)];
+    let edits = delta_edits(&old, &new);
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].start, 5);
+    assert_eq!(edits[0].delete_count, 10);
+    assert_eq!(edits[0].data, Some(vec![t(5), t(6)]));
+    // insert only.
+    let edits = delta_edits(&old[..2], &old);
+    assert_eq!(edits[0].start, 10);
+    assert_eq!(edits[0].delete_count, 0);
+    assert_eq!(edits[0].data, Some(vec![t(3), t(4)]));
+    assert!(delta_edits(&old, &old).is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn test_legend() {
+    let l = legend();
+    assert_eq!(
+        l.token_types.len(),
+        enum_iterator::cardinality::<TokenTypes>()
+    );
+    assert_eq!(
+        l.token_types[TokenTypes::EnumMember.to_u32() as usize],
+        SemanticTokenType::ENUM_MEMBER
+    );
+    assert_eq!(l.token_modifiers[1], SemanticTokenModifier::DEFINITION);
+    assert_eq!(TokenModifier::Readonly.to_u32(), 4);
+}
+
+#[cfg(test)]
+#[test]
+fn test_resolved_tokens() {
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("tests")
+        .join("semantic_tokens.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let tokens = range_tokens(&p, &file).unwrap();
+    let at = |line: u32, col: u32| {
+        let t = tokens
+            .iter()
+            .find(|t| t.range.start == Position::new(line, col))
+            .unwrap();
+        (t.token_type.to_u32(), t.modifiers)
+    };
+    let declaration = TokenModifier::Declaration.to_u32();
+    let definition = TokenModifier::Definition.to_u32();
+    let readonly = TokenModifier::Readonly.to_u32();
+    let ext = TokenModifier::Extern.to_u32();
+    // (decl pure ext_ctor (u32) u32)
+    assert_eq!(
+        at(4, 11),
+        (
+            TokenTypes::Constructor.to_u32(),
+            declaration | readonly | ext
+        )
+    );
+    // (extern constructor ext_ctor ext_ctor)
+    assert_eq!(
+        at(5, 20),
+        (
+            TokenTypes::Constructor.to_u32(),
+            readonly | ext | definition
+        )
+    );
+    assert_eq!(at(5, 29), (TokenTypes::Function.to_u32(), ext));
+    // (decl is_zero (u32) u32) with an extractor.
+    assert_eq!(at(6, 6), (TokenTypes::Extractor.to_u32(), declaration));
+    // (decl f (Opt) u32) with rules.
+    assert_eq!(at(9, 6), (TokenTypes::Constructor.to_u32(), declaration));
+    // (rule (f (Opt.Some x)) ...
+    assert_eq!(at(10, 7), (TokenTypes::Constructor.to_u32(), definition));
+    assert_eq!(at(10, 10), (TokenTypes::Type.to_u32(), 0));
+    assert_eq!(at(10, 14), (TokenTypes::EnumMember.to_u32(), 0));
+    assert_eq!(at(10, 19), (TokenTypes::Variable.to_u32(), declaration));
+    // (ext_ctor x)
+    assert_eq!(
+        at(10, 24),
+        (TokenTypes::Constructor.to_u32(), readonly | ext)
+    );
+    assert_eq!(at(10, 33), (TokenTypes::Variable.to_u32(), 0));
+    // (rule (f (Opt.Nome)) $ZERO)
+    assert_eq!(at(11, 14), (TokenTypes::Unresolved.to_u32(), 0));
+    assert_eq!(at(11, 21), (TokenTypes::Variable.to_u32(), readonly));
+    // (rule (f (is_zro _)) 1)
+    assert_eq!(at(12, 10), (TokenTypes::Unresolved.to_u32(), 0));
+    assert_eq!(at(12, 21), (TokenTypes::Number.to_u32(), 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_range_tokens() {
+    let file = std::env::current_dir()
+        .unwrap()
+        .join("tests")
+        .join("semantic_tokens.isle");
+    let p = Project::new(vec![file.clone()]).unwrap();
+    let tokens = range_tokens(&p, &file).unwrap();
+    // `type` keyword first.
+    assert_eq!(tokens[0].token_type.to_u32(), TokenTypes::Keyword.to_u32());
+    assert_eq!(tokens[0].range.start, Position::new(0, 1));
+    let tokens = tokens_in_range(
+        tokens,
+        &Range {
+            start: Position::new(9, 0),
+            end: Position::new(10, 0),
+        },
+    );
+    assert!(!tokens.is_empty());
+    assert!(tokens.iter().all(|t| t.range.start.line == 9));
+    // not part of the project.
+    assert!(range_tokens(&p, &file.with_file_name("none.isle")).is_none());
+}
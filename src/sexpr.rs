@@ -0,0 +1,265 @@
+//! `ISLE` source as a tree of s-expressions.
+//! Unlike the `AST` this keep the position of every parenthesis,
+//! which is what editor features like folding need.
+
+use cranelift_isle::{
+    error::Errors,
+    lexer::{Lexer, Pos, Token},
+};
+use lsp_types::{Position, Range};
+
+#[derive(Clone, Debug)]
+pub(crate) enum SExprKind {
+    /// A symbol,a integer or `@`.
+    Atom(String),
+    List(Vec<SExpr>),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct SExpr {
+    pub(crate) start: Pos,
+    /// Position just after the last character.
+    pub(crate) end: Pos,
+    pub(crate) kind: SExprKind,
+}
+
+impl SExpr {
+    pub(crate) fn children(&self) -> &[SExpr] {
+        match &self.kind {
+            SExprKind::Atom(_) => &[],
+            SExprKind::List(x) => &x[..],
+        }
+    }
+
+    /// Symbol at first of a list, like `rule` in `(rule ...)`.
+    pub(crate) fn head(&self) -> Option<&str> {
+        match self.children().first().map(|x| &x.kind) {
+            Some(SExprKind::Atom(s)) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn atom(&self) -> Option<&str> {
+        match &self.kind {
+            SExprKind::Atom(s) => Some(s.as_str()),
+            SExprKind::List(_) => None,
+        }
+    }
+
+    pub(crate) fn contains(&self, offset: usize) -> bool {
+        self.start.offset <= offset && offset <= self.end.offset
+    }
+
+    /// Like `contains` but `pos` may come from a multi-file project.
+    pub(crate) fn contains_pos(&self, pos: &Pos) -> bool {
+        (self.start.line, self.start.col) <= (pos.line, pos.col)
+            && (pos.line, pos.col) <= (self.end.line, self.end.col)
+    }
+
+    /// Zero-based start line.
+    pub(crate) fn start_line(&self) -> u32 {
+        (self.start.line - 1) as u32
+    }
+
+    /// Zero-based end line.
+    pub(crate) fn end_line(&self) -> u32 {
+        (self.end.line - 1) as u32
+    }
+
+    pub(crate) fn range(&self) -> Range {
+        Range {
+            start: pos_to_position(&self.start),
+            end: pos_to_position(&self.end),
+        }
+    }
+
+    /// Source text of this expression.
+    pub(crate) fn text<'a>(&self, content: &'a str) -> &'a str {
+        content
+            .get(self.start.offset..self.end.offset)
+            .unwrap_or("")
+    }
+}
+
+pub(crate) fn pos_to_position(x: &Pos) -> Position {
+    Position {
+        line: (x.line - 1) as u32,
+        character: x.col as u32,
+    }
+}
+
+/// Byte offset of a `LSP` position in `content`.
+pub(crate) fn position_to_offset(content: &str, p: &Position) -> usize {
+    let mut offset = 0;
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        if index as u32 == p.line {
+            return offset + (p.character as usize).min(line.trim_end_matches('\n').len());
+        }
+        offset += line.len();
+    }
+    offset
+}
+
+/// Byte offset of `pos` in `content`,computed from line and column
+/// since `Pos::offset` of a multi-file project may not be relative to the file.
+pub(crate) fn local_offset(content: &str, pos: &Pos) -> usize {
+    position_to_offset(content, &pos_to_position(pos))
+}
+
+/// Position of `len` bytes after `pos` on the same line.
+fn advance(pos: &Pos, len: usize) -> Pos {
+    Pos {
+        file: pos.file,
+        offset: pos.offset + len,
+        line: pos.line,
+        col: pos.col + len,
+    }
+}
+
+/// Parse `content` into s-expressions.
+/// Unbalanced parentheses are tolerated so this works while the user is typing.
+pub(crate) fn parse_sexprs(content: &str, filename: &str) -> Result<Vec<SExpr>, Errors> {
+    let mut lexer = Lexer::from_str(content, filename)?;
+    let mut top = Vec::new();
+    // opening position and children of lists not closed yet.
+    let mut stack: Vec<(Pos, Vec<SExpr>)> = Vec::new();
+    let mut last_end = None;
+    while let Some((pos, t)) = lexer.next()? {
+        let atom = |s: String| {
+            let len = s.len();
+            SExpr {
+                start: pos,
+                end: advance(&pos, len),
+                kind: SExprKind::Atom(s),
+            }
+        };
+        let e = match t {
+            Token::LParen => {
+                stack.push((pos, vec![]));
+                continue;
+            }
+            Token::RParen => match stack.pop() {
+                Some((start, children)) => SExpr {
+                    start,
+                    end: advance(&pos, 1),
+                    kind: SExprKind::List(children),
+                },
+                None => continue,
+            },
+            Token::Symbol(s) => atom(s),
+            Token::Int(i) => {
+                let s = content
+                    .get(pos.offset..)
+                    .map(|rest| {
+                        let end = rest
+                            .find(|c: char| {
+                                c.is_ascii_whitespace() || c == '(' || c == ')' || c == ';'
+                            })
+                            .unwrap_or(rest.len());
+                        rest[..end].to_string()
+                    })
+                    .unwrap_or(i.to_string());
+                atom(s)
+            }
+            Token::At => atom("@".to_string()),
+        };
+        last_end = Some(e.end);
+        match stack.last_mut() {
+            Some((_, children)) => children.push(e),
+            None => top.push(e),
+        }
+    }
+    // close lists that has no `)`.
+    while let Some((start, children)) = stack.pop() {
+        let e = SExpr {
+            start,
+            end: last_end.unwrap_or(start),
+            kind: SExprKind::List(children),
+        };
+        match stack.last_mut() {
+            Some((_, x)) => x.push(e),
+            None => top.push(e),
+        }
+    }
+    Ok(top)
+}
+
+pub(crate) enum RulePrio<'a> {
+    /// The priority atom.
+    Explicit(&'a SExpr),
+    /// No priority,it would be written after this atom.
+    After(&'a SExpr),
+}
+
+/// Priority of `(rule name? prio? pattern expr...)`.
+pub(crate) fn rule_prio(form: &SExpr) -> Option<RulePrio<'_>> {
+    let children = form.children();
+    if form.head() != Some("rule") || children.len() < 3 {
+        return None;
+    }
+    let is_int = |x: &SExpr| {
+        x.atom()
+            .map(|x| {
+                x.trim_start_matches('-')
+                    .starts_with(|c: char| c.is_ascii_digit())
+            })
+            .unwrap_or(false)
+    };
+    let named = children[1].atom().map(|x| x != "@").unwrap_or(false)
+        && !is_int(&children[1])
+        && children.len() > 3;
+    let index = if named { 2 } else { 1 };
+    if is_int(&children[index]) {
+        Some(RulePrio::Explicit(&children[index]))
+    } else {
+        Some(RulePrio::After(&children[index - 1]))
+    }
+}
+
+/// Text of list `e` with children replaced by `f`,spaces and comments are kept.
+pub(crate) fn splice(
+    content: &str,
+    e: &SExpr,
+    mut f: impl FnMut(usize, &SExpr) -> String,
+) -> String {
+    let children = e.children();
+    if children.is_empty() {
+        return e.text(content).to_string();
+    }
+    let mut ret = String::new();
+    let mut last = e.start.offset;
+    for (index, c) in children.iter().enumerate() {
+        ret.push_str(&content[last..c.start.offset]);
+        ret.push_str(f(index, c).as_str());
+        last = c.end.offset;
+    }
+    ret.push_str(&content[last..e.end.offset]);
+    ret
+}
+
+/// Expressions enclosing `offset`,from the outermost to the innermost.
+pub(crate) fn enclosing(forms: &[SExpr], offset: usize) -> Vec<&SExpr> {
+    let mut ret = Vec::new();
+    let mut forms = forms;
+    while let Some(x) = forms.iter().find(|x| x.contains(offset)) {
+        ret.push(x);
+        forms = x.children();
+    }
+    ret
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_sexprs() {
+    let content = "(decl A (u32) u32)\n(rule (A 0x10)\n  (B x @ y))";
+    let forms = parse_sexprs(content, "").unwrap();
+    assert_eq!(forms.len(), 2);
+    assert_eq!(forms[0].head(), Some("decl"));
+    assert_eq!(forms[1].text(content), "(rule (A 0x10)\n  (B x @ y))");
+    assert_eq!(forms[1].start_line(), 1);
+    assert_eq!(forms[1].end_line(), 2);
+    let path = enclosing(&forms, content.find("0x10").unwrap() + 1);
+    assert_eq!(path.len(), 3);
+    assert_eq!(path[2].atom(), Some("0x10"));
+    assert_eq!(path[1].text(content), "(A 0x10)");
+}
@@ -0,0 +1,131 @@
+//! Variables bound in the pattern or a `if-let` of a rule but never used.
+//! A variable appears again in a pattern is a equality constraint,which is a use.
+
+use super::lint::*;
+use super::project::*;
+use super::utils::pattern_pos;
+use cranelift_isle::ast::*;
+use cranelift_isle::lexer::Pos;
+
+pub(crate) struct UnusedVar {
+    pub(crate) var: Ident,
+    /// Start of `subpat` for `var @ subpat`.
+    pub(crate) subpat: Option<Pos>,
+}
+
+/// Unused variables of all rules in `provider`.
+pub(crate) fn unused_vars(provider: impl AstProvider) -> Vec<UnusedVar> {
+    let mut ret = Vec::new();
+    provider.with_rule(|r| {
+        let mut s = Scope::default();
+        s.pattern(&r.pattern);
+        for i in r.iflets.iter() {
+            s.expr(&i.expr);
+            s.pattern(&i.pattern);
+        }
+        s.expr(&r.expr);
+        for (v, used) in s.vars.into_iter() {
+            // `_x` is unused on purpose.
+            if !used && !v.var.0.starts_with('_') {
+                ret.push(v);
+            }
+        }
+    });
+    ret
+}
+
+#[derive(Default)]
+struct Scope {
+    /// Names in scope,innermost last,
+    /// with the index into `vars` or `None` for `let` variables.
+    bound: Vec<(String, Option<usize>)>,
+    vars: Vec<(UnusedVar, bool)>,
+}
+
+impl Scope {
+    fn lookup(&self, name: &str) -> Option<Option<usize>> {
+        self.bound
+            .iter()
+            .rev()
+            .find(|(x, _)| x == name)
+            .map(|(_, index)| *index)
+    }
+
+    fn use_var(&mut self, name: &str) {
+        if let Some(Some(index)) = self.lookup(name) {
+            self.vars[index].1 = true;
+        }
+    }
+
+    fn bind(&mut self, var: &Ident, subpat: Option<Pos>) {
+        if self.lookup(&var.0).is_some() {
+            // equality constraint.
+            self.use_var(&var.0);
+            return;
+        }
+        self.bound.push((var.0.clone(), Some(self.vars.len())));
+        self.vars.push((
+            UnusedVar {
+                var: var.clone(),
+                subpat,
+            },
+            false,
+        ));
+    }
+
+    fn pattern(&mut self, pat: &Pattern) {
+        match pat {
+            Pattern::Var { var, .. } => self.bind(var, None),
+            Pattern::BindPattern { var, subpat, .. } => {
+                self.bind(var, Some(pattern_pos(subpat.as_ref())));
+                self.pattern(subpat.as_ref());
+            }
+            Pattern::Term { args, .. } => args.iter().for_each(|x| self.pattern(x)),
+            Pattern::And { subpats, .. } => subpats.iter().for_each(|x| self.pattern(x)),
+            Pattern::ConstInt { .. }
+            | Pattern::ConstPrim { .. }
+            | Pattern::Wildcard { .. }
+            | Pattern::MacroArg { .. } => {}
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Var { name, .. } => self.use_var(&name.0),
+            Expr::Term { args, .. } => args.iter().for_each(|x| self.expr(x)),
+            Expr::Let { defs, body, .. } => {
+                let len = self.bound.len();
+                for d in defs.iter() {
+                    self.expr(d.val.as_ref());
+                    self.bound.push((d.var.0.clone(), None));
+                }
+                self.expr(body.as_ref());
+                self.bound.truncate(len);
+            }
+            Expr::ConstInt { .. } | Expr::ConstPrim { .. } => {}
+        }
+    }
+}
+
+/// Warn on variables never used.
+pub struct UnusedVariables;
+
+impl Lint for UnusedVariables {
+    fn name(&self) -> &'static str {
+        "unused-variable"
+    }
+    fn default_level(&self) -> LintLevel {
+        LintLevel::Warning
+    }
+    fn check(&self, p: &Project, results: &mut Vec<LintResult>) {
+        for v in unused_vars(ProjectAstProvider::new(p)).into_iter() {
+            if let Some(loc) = p.mk_location(&v.var) {
+                results.push(LintResult {
+                    loc,
+                    message: format!("unused variable `{}`", v.var.0),
+                    tags: vec![lsp_types::DiagnosticTag::UNNECESSARY],
+                });
+            }
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use cranelift_isle::{
-    ast::{Ident, Type},
+    ast::{Expr, Ident, Pattern, Type},
     lexer::Pos,
 };
 use lsp_types::Location;
@@ -124,3 +124,26 @@ impl GetPosAndLength for Type {
         (self.name.1, self.name.0.len() as u32)
     }
 }
+
+pub(crate) fn pattern_pos(p: &Pattern) -> Pos {
+    match p {
+        Pattern::Var { pos, .. }
+        | Pattern::BindPattern { pos, .. }
+        | Pattern::ConstInt { pos, .. }
+        | Pattern::ConstPrim { pos, .. }
+        | Pattern::Term { pos, .. }
+        | Pattern::Wildcard { pos }
+        | Pattern::And { pos, .. }
+        | Pattern::MacroArg { pos, .. } => *pos,
+    }
+}
+
+pub(crate) fn expr_pos(e: &Expr) -> Pos {
+    match e {
+        Expr::Term { pos, .. }
+        | Expr::Var { pos, .. }
+        | Expr::ConstInt { pos, .. }
+        | Expr::ConstPrim { pos, .. }
+        | Expr::Let { pos, .. } => *pos,
+    }
+}
@@ -0,0 +1,4 @@
+// Rust side of `dead_code.isle`,`lower` is the entry.
+pub fn lower(ctx: &mut IsleContext, e: E) -> u32 {
+    generated_code::constructor_lower(ctx, e)
+}